@@ -6,14 +6,15 @@
 //! Therefore, it needs to schedule the machine-level timer interrupts via the [`MTIMECMP`] register assigned to the current HART.
 //! Thus, the [`Delay`] instance must be created on the same HART that is used to call the asynchronous delay methods.
 
-use core::cell::RefCell;
-use core::future::poll_fn;
+use core::cell::{Cell, RefCell};
+use core::future::{poll_fn, Future};
 use core::{
     cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd},
     task::{Poll, Waker},
 };
 use critical_section::Mutex;
 use e310x::{interrupt::Hart, Clint};
+use embassy_futures::select::{select, Either};
 use embedded_hal_async::delay::DelayNs;
 use heapless::binary_heap::{BinaryHeap, Min};
 use riscv_peripheral::aclint::mtimer::MTIMER;
@@ -21,18 +22,77 @@ use riscv_peripheral::aclint::mtimer::MTIMER;
 const N_TIMERS: usize = 16;
 static TIMER_QUEUE: Mutex<RefCell<BinaryHeap<Timer, Min, N_TIMERS>>> =
     Mutex::new(RefCell::new(BinaryHeap::new()));
+/// Identifier of the next timer pushed to the timer queue
+static NEXT_TIMER_ID: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
 
 /// Added a get Mtimer function for convenience
 /// Tries to push a new timer to the timer queue assigned to the `MTIMER` register for the current HART ID.
 /// If it fails (e.g., the timer queue is full), it returns back the timer that failed to be pushed.
+/// Otherwise, it returns the identifier of the timer, which can be used to remove it from the queue.
 #[inline]
-pub(crate) fn aclint_push_timer(t: Timer) -> Result<(), Timer> {
+pub(crate) fn aclint_push_timer(mut t: Timer) -> Result<TimerId, Timer> {
     critical_section::with(|cs| {
+        let id = NEXT_TIMER_ID.borrow(cs).get();
+        NEXT_TIMER_ID.borrow(cs).set(id.wrapping_add(1));
+        t.id = TimerId(id);
         let mut timer_queue = TIMER_QUEUE.borrow_ref_mut(cs);
-        timer_queue.push(t)
+        timer_queue.push(t)?;
+        Ok(TimerId(id))
     })
 }
 
+/// Removes a timer from the timer queue, if it has not expired yet.
+#[inline]
+pub(crate) fn aclint_remove_timer(id: TimerId) {
+    critical_section::with(|cs| {
+        let mut timer_queue = TIMER_QUEUE.borrow_ref_mut(cs);
+        if timer_queue.iter().all(|t| t.id != id) {
+            return;
+        }
+        let mut kept = BinaryHeap::new();
+        while let Some(t) = timer_queue.pop() {
+            if t.id != id {
+                // Cannot fail, as `kept` has the same capacity
+                let _ = kept.push(t);
+            }
+        }
+        *timer_queue = kept;
+    })
+}
+
+/// Pushes a timer for `waker` at `expires`, or moves the timer already queued for the same
+/// waker to `expires` if it is earlier. Used by drivers that cannot remove their timers.
+///
+/// Returns false if the timer queue is full.
+#[cfg(feature = "embassy")]
+pub(crate) fn aclint_push_or_update_timer(expires: u64, waker: &Waker) -> bool {
+    let updated = critical_section::with(|cs| {
+        let mut timer_queue = TIMER_QUEUE.borrow_ref_mut(cs);
+        let Some(t) = timer_queue
+            .iter()
+            .find(|t| t.per_waker && t.waker.will_wake(waker))
+        else {
+            return false;
+        };
+        if t.expires > expires {
+            let id = t.id;
+            let mut kept = BinaryHeap::new();
+            while let Some(mut t) = timer_queue.pop() {
+                if t.id == id {
+                    t.expires = expires;
+                }
+                // Cannot fail, as `kept` has the same capacity
+                let _ = kept.push(t);
+            }
+            *timer_queue = kept;
+        }
+        true
+    });
+    let mut timer = Timer::new(expires, waker.clone());
+    timer.per_waker = true;
+    updated || aclint_push_timer(timer).is_ok()
+}
+
 /// Pops all the expired timers from the timer queue assigned to the `MTIMER` register for the
 /// current HART ID and wakes their associated wakers. Once it is done, if the queue is empty,
 /// it returns `None`. Alternatively, if the queue is not empty but the earliest timer has not expired
@@ -75,6 +135,10 @@ pub(crate) fn schedule_machine_timer(mtimer: &MTIMER<Clint>) {
     }
 }
 
+/// Identifier of a timer queue entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TimerId(usize);
+
 /// Timer queue entry.
 ///
 /// When pushed to the timer queue via the `aclint_push_timer` function,
@@ -82,6 +146,10 @@ pub(crate) fn schedule_machine_timer(mtimer: &MTIMER<Clint>) {
 #[derive(Debug)]
 pub(crate) struct Timer {
     expires: u64,
+    id: TimerId,
+    /// Whether the timer is shared by every deadline of its waker
+    #[cfg(feature = "embassy")]
+    per_waker: bool,
     waker: Waker,
 }
 
@@ -89,7 +157,13 @@ impl Timer {
     /// Creates a new timer queue entry.
     #[inline]
     pub(crate) const fn new(expires: u64, waker: Waker) -> Self {
-        Self { expires, waker }
+        Self {
+            expires,
+            id: TimerId(0),
+            #[cfg(feature = "embassy")]
+            per_waker: false,
+            waker,
+        }
     }
 
     /// Returns the expiration tick of the timer.
@@ -125,6 +199,51 @@ impl PartialOrd for Timer {
     }
 }
 
+/// Timer queue entry owned by an asynchronous operation.
+///
+/// The entry is removed from the timer queue when the timer is re-armed or dropped,
+/// so that completed or cancelled operations do not leave stale timers behind.
+pub(crate) struct TimerSlot {
+    id: Option<TimerId>,
+}
+
+impl TimerSlot {
+    /// Creates a disarmed timer slot.
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Self { id: None }
+    }
+
+    /// Arms the timer to wake `waker` at `expires`, replacing the previous deadline, if any.
+    ///
+    /// Returns false if the timer queue is full. In that case, the caller must poll instead.
+    pub(crate) fn arm(&mut self, mtimer: &MTIMER<Clint>, expires: u64, waker: &Waker) -> bool {
+        self.disarm();
+        match aclint_push_timer(Timer::new(expires, waker.clone())) {
+            Ok(id) => {
+                self.id = Some(id);
+                schedule_machine_timer(mtimer);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Removes the timer from the timer queue, if any.
+    #[inline]
+    pub(crate) fn disarm(&mut self) {
+        if let Some(id) = self.id.take() {
+            aclint_remove_timer(id);
+        }
+    }
+}
+
+impl Drop for TimerSlot {
+    fn drop(&mut self) {
+        self.disarm();
+    }
+}
+
 /// async delay trait implementation from `embedded-hal-async`
 #[derive(Clone)]
 pub struct Delay {
@@ -139,20 +258,19 @@ impl Delay {
     }
 
     /// Delays for the given number of ticks.
+    ///
+    /// The timer is removed from the timer queue when the future completes or is dropped.
+    /// If the timer queue is full, the future polls the `MTIME` register instead.
     #[inline]
-    async fn delay_ticks(&mut self, n_ticks: u64) {
+    pub(crate) async fn delay_ticks(&mut self, n_ticks: u64) {
         let mtime = self.mtimer.mtime();
         let expires = mtime.read() + n_ticks;
-        let mut pushed = false;
-        poll_fn(move |cx| {
+        let mut slot = TimerSlot::new();
+        poll_fn(|cx| {
             if mtime.read() < expires {
-                if !pushed {
-                    // Push timer to queue only on first pending poll
-                    pushed = true;
-                    let timer = Timer::new(expires, cx.waker().clone());
-                    let _ = aclint_push_timer(timer);
-                    // Schedule machine timer interrupt
-                    schedule_machine_timer(&self.mtimer);
+                // Push timer to queue only on first pending poll (or until it fits in the queue)
+                if slot.id.is_none() && !slot.arm(&self.mtimer, expires, cx.waker()) {
+                    cx.waker().wake_by_ref();
                 }
                 Poll::Pending
             } else {
@@ -161,6 +279,21 @@ impl Delay {
         })
         .await;
     }
+
+    /// Runs `future` until it completes or the given number of ticks elapse.
+    ///
+    /// Returns `None` if the timeout expires first, in which case `future` is dropped.
+    /// The timer is removed from the timer queue when `future` completes first.
+    pub(crate) async fn with_timeout<F: Future>(
+        &mut self,
+        n_ticks: u64,
+        future: F,
+    ) -> Option<F::Output> {
+        match select(future, self.delay_ticks(n_ticks)).await {
+            Either::First(output) => Some(output),
+            Either::Second(()) => None,
+        }
+    }
 }
 
 impl DelayNs for Delay {
@@ -41,6 +41,19 @@ macro_rules! gpio_async {
         /// Interrupt handler for GPIO pins.
         #[inline]
         fn on_irq(pin_n: usize) {
+            // Baud rate detections timestamp every edge of the RX pin
+            if crate::serial::on_autobaud_irq(pin_n) {
+                return;
+            }
+            // Software serial receivers keep their interrupt enabled
+            if crate::serial::on_soft_rx_irq(pin_n) {
+                return;
+            }
+            // CTS lines of serial interfaces with flow control wake the pending write
+            if crate::asynch::serial::on_cts_irq(pin_n) {
+                return;
+            }
+
             let gpio_block = unsafe { $GPIOX::steal() };
             let pin_mask = 1 << pin_n;
 
@@ -2,7 +2,7 @@
 //!
 //! This module provides the driver for embassy-time, keeping track of time and managing the
 //! wait queue.
-use crate::asynch::delay::{aclint_push_timer, schedule_machine_timer, Timer};
+use crate::asynch::delay::{aclint_push_or_update_timer, schedule_machine_timer};
 use core::task::Waker;
 use e310x::Clint;
 use embassy_time_driver::Driver;
@@ -16,9 +16,13 @@ impl Driver for MyDriver {
         mtimer.mtime().read()
     }
     fn schedule_wake(&self, at: u64, waker: &Waker) {
-        let timer = Timer::new(at, waker.clone());
+        // Keep one timer per task, as embassy-time cannot remove its timers
+        if !aclint_push_or_update_timer(at, waker) {
+            // The timer queue is full, poll instead of losing the wake-up
+            waker.wake_by_ref();
+            return;
+        }
         let mtimer = unsafe { Clint::steal() }.mtimer();
-        let _ = aclint_push_timer(timer);
         // Schedule machine timer interrupt
         schedule_machine_timer(&mtimer);
     }
@@ -3,15 +3,27 @@
 //!
 //! Implementation of the Async Embedded HAL I2C functionality.
 //!
-use crate::i2c::{I2c, I2cX};
+//! Transactions are cancelled after the timeout set with [`I2c::set_timeout`], if any.
+//! [`I2c::shared_async`] wraps the bus in a [`SharedI2c`] behind an `embassy-sync` mutex,
+//! which hands out [`I2cDevice`] handles for async drivers.
+//! [`I2c::scan_async`] and [`I2c::probe_async`] are the async counterparts of [`I2c::scan`] and [`I2c::probe`].
+//!
+use crate::asynch::delay::Delay;
+use crate::i2c::{duration_ticks, Error, I2c, I2cX};
 use core::cell::RefCell;
 use core::future::poll_fn;
 use core::task::{Poll, Waker};
 use critical_section::Mutex;
-use e310x::I2c0;
-use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource, Operation};
+use e310x::{Clint, I2c0};
+use embedded_hal::i2c::{NoAcknowledgeSource, Operation};
 use embedded_hal_async::i2c;
 
+mod scan; // contains the async bus scanner and device probing
+mod shared; // contains the shared I2C bus and device abstractions
+mod ten_bit; // contains the async 10-bit addressing implementation
+
+pub use shared::*;
+
 const FLAG_READ: u8 = 1;
 const FLAG_WRITE: u8 = 0;
 static I2C_WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
@@ -37,7 +49,7 @@ impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
     }
 
     /// Acknowledge the I2C interrupt.
-    async fn ack_interrupt_async(&mut self) -> Result<(), ErrorKind> {
+    async fn ack_interrupt_async(&mut self) -> Result<(), Error> {
         poll_fn(|cx| {
             let result = self.ack_interrupt();
             match result {
@@ -59,28 +71,36 @@ impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
     }
 
     /// Asynchronously wait for a write operation to complete.
-    async fn wait_for_write_async(&mut self, source: NoAcknowledgeSource) -> Result<(), ErrorKind> {
+    async fn wait_for_write_async(&mut self, source: NoAcknowledgeSource) -> Result<(), Error> {
         self.ack_interrupt_async().await?;
         if self.read_sr().rx_ack().bit_is_set() {
             self.set_stop();
-            Err(ErrorKind::NoAcknowledge(source))
+            Err(Error::NoAcknowledge(source))
         } else {
             Ok(())
         }
     }
 
     /// Asynchronously wait for a read operation to complete.
-    async fn wait_for_read_async(&mut self) -> Result<(), ErrorKind> {
+    async fn wait_for_read_async(&mut self) -> Result<(), Error> {
         self.ack_interrupt_async().await
     }
-}
 
-impl<I2C: I2cX, PINS> i2c::I2c for I2c<I2C, PINS> {
-    async fn transaction(
+    /// Cleans up after a transaction cancelled by its timeout.
+    ///
+    /// The controller ignores STOP requests while a transfer is in progress, so the bus
+    /// is left as is and must be released with [`I2c::recover_bus`].
+    fn abort_async(&mut self) {
+        self.disable_interrupt();
+        critical_section::with(|cs| I2C_WAKER.borrow_ref_mut(cs).take());
+    }
+
+    /// Performs a transaction without timeout.
+    async fn transaction_async(
         &mut self,
         address: u8,
         operations: &mut [i2c::Operation<'_>],
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Error> {
         let n_ops = operations.len();
         if n_ops == 0 {
             return Ok(());
@@ -135,6 +155,30 @@ impl<I2C: I2cX, PINS> i2c::I2c for I2c<I2C, PINS> {
     }
 }
 
+impl<I2C: I2cX, PINS> i2c::I2c for I2c<I2C, PINS> {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let Some(timeout) = self.timeout else {
+            return self.transaction_async(address, operations).await;
+        };
+        let mut delay = Delay::new(unsafe { Clint::steal() }.mtimer());
+        let transaction = self.transaction_async(address, operations);
+        match delay
+            .with_timeout(duration_ticks(timeout), transaction)
+            .await
+        {
+            Some(result) => result,
+            None => {
+                self.abort_async();
+                Err(Error::Timeout)
+            }
+        }
+    }
+}
+
 /// Interrupt Handler
 #[riscv_rt::external_interrupt(e310x::interrupt::ExternalInterrupt::I2C0)]
 fn i2c_handler() {
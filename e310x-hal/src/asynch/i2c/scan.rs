@@ -0,0 +1,99 @@
+use super::FLAG_WRITE;
+use crate::{
+    asynch::delay::Delay,
+    i2c::{duration_ticks, Error, I2c, I2cX, ScanResult, SCAN_FIRST, SCAN_LAST},
+};
+use core::time::Duration;
+use e310x::Clint;
+use embedded_hal::i2c::NoAcknowledgeSource;
+
+impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
+    /// Asynchronously probes the device at a 7-bit `address` with a zero-length write.
+    ///
+    /// Returns `true` if the device acknowledged its address, and `false` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::ArbitrationLoss`] - another master took the bus
+    /// * [`Error::Timeout`] - the probe did not complete within the timeout set with [`I2c::set_timeout`]
+    pub async fn probe_async(&mut self, address: u8) -> Result<bool, Error> {
+        self.probe_timeout_async(address, self.timeout, &mut false)
+            .await
+    }
+
+    /// Asynchronously probes every non-reserved 7-bit address (`0x08` to `0x77`) with a zero-length write.
+    ///
+    /// Each address is given `timeout` to complete its probe. Addresses that time out once
+    /// addressed are reported as absent, and the bus is released with [`I2c::recover_bus`],
+    /// so a misbehaving device cannot hang the scan.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::ArbitrationLoss`] - another master took the bus
+    /// * [`Error::Timeout`] - the bus is not idle before a probe (e.g., SDA is held low)
+    /// * [`Error::BusStuck`] - the bus cannot be released after a device timed out
+    pub async fn scan_async(&mut self, timeout: Duration) -> Result<ScanResult, Error> {
+        let mut result = ScanResult::default();
+        for address in SCAN_FIRST..=SCAN_LAST {
+            let mut addressed = false;
+            match self
+                .probe_timeout_async(address, Some(timeout), &mut addressed)
+                .await
+            {
+                Ok(true) => result.insert(address),
+                Ok(false) => {}
+                Err(Error::Timeout) if addressed => self.recover_bus()?,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Probes an address, cancelling the probe after `timeout`, if any.
+    ///
+    /// `addressed` is set once the bus is idle and the address is sent, to tell a busy bus
+    /// from a device that does not complete its probe.
+    async fn probe_timeout_async(
+        &mut self,
+        address: u8,
+        timeout: Option<Duration>,
+        addressed: &mut bool,
+    ) -> Result<bool, Error> {
+        let Some(timeout) = timeout else {
+            return self.probe_inner_async(address, addressed).await;
+        };
+        let mut delay = Delay::new(unsafe { Clint::steal() }.mtimer());
+        let probe = self.probe_inner_async(address, addressed);
+        match delay.with_timeout(duration_ticks(timeout), probe).await {
+            Some(result) => result,
+            None => {
+                self.abort_async();
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Sends the address with START and STOP conditions, and waits for the bus to be idle.
+    async fn probe_inner_async(
+        &mut self,
+        address: u8,
+        addressed: &mut bool,
+    ) -> Result<bool, Error> {
+        self.wait_idle_async().await;
+        self.reset();
+        *addressed = true;
+
+        self.write_txr((address << 1) + FLAG_WRITE);
+        self.trigger_write(true, true);
+        let present = match self
+            .wait_for_write_async(NoAcknowledgeSource::Address)
+            .await
+        {
+            Ok(()) => true,
+            Err(Error::NoAcknowledge(_)) => false,
+            Err(e) => return Err(e),
+        };
+        self.wait_idle_async().await;
+        Ok(present)
+    }
+}
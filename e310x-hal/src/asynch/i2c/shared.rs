@@ -0,0 +1,97 @@
+use crate::{
+    clock::Clocks,
+    i2c::{prescaler, Error, FrequencyError, I2c, I2cX},
+    time::Hertz,
+};
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+use embedded_hal::i2c::{ErrorType, Operation};
+
+impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
+    /// Create a [`SharedI2c`] for use with multiple async devices.
+    pub fn shared_async<M: RawMutex>(self) -> SharedI2c<M, I2C, PINS> {
+        SharedI2c::new(self)
+    }
+}
+
+/// Used to hold the [I2c] instance so it can be used for multiple [I2cDevice] instances.
+pub struct SharedI2c<M: RawMutex, I2C, PINS> {
+    bus: Mutex<M, I2c<I2C, PINS>>,
+    /// Prescaler and frequency of the devices without a specific frequency
+    speed: (u32, Hertz),
+}
+
+impl<M: RawMutex, I2C: I2cX, PINS> SharedI2c<M, I2C, PINS> {
+    pub(crate) fn new(bus: I2c<I2C, PINS>) -> Self {
+        let speed = bus.speed();
+        Self {
+            bus: Mutex::new(bus),
+            speed,
+        }
+    }
+
+    /// Create a new shared device on this I2C bus, using the frequency of the bus when it was shared.
+    pub fn new_device(&self) -> I2cDevice<'_, M, I2C, PINS> {
+        I2cDevice {
+            bus: self,
+            speed: self.speed,
+        }
+    }
+
+    /// Create a new shared device on this I2C bus with its own bus frequency.
+    ///
+    /// The frequency is set every time the device acquires the bus, if it differs from the current one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frequency cannot be reached. See [`I2c::with_frequency`] for more details.
+    pub fn new_device_with_frequency(
+        &self,
+        frequency: Hertz,
+        clocks: Clocks,
+    ) -> Result<I2cDevice<'_, M, I2C, PINS>, FrequencyError> {
+        Ok(I2cDevice {
+            bus: self,
+            speed: prescaler(frequency, &clocks)?,
+        })
+    }
+
+    /// Lock the Mutex to access the underlying I2c
+    pub async fn lock(&self) -> MutexGuard<'_, M, I2c<I2C, PINS>> {
+        self.bus.lock().await
+    }
+
+    /// Releases the I2C bus
+    pub fn release(self) -> I2c<I2C, PINS> {
+        self.bus.into_inner()
+    }
+}
+
+/// I2C shared device abstraction for async drivers
+pub struct I2cDevice<'bus, M: RawMutex, I2C, PINS> {
+    bus: &'bus SharedI2c<M, I2C, PINS>,
+    speed: (u32, Hertz),
+}
+
+impl<M: RawMutex, I2C, PINS> I2cDevice<'_, M, I2C, PINS> {
+    /// Returns the bus frequency used by this device.
+    pub fn frequency(&self) -> Hertz {
+        self.speed.1
+    }
+}
+
+impl<M: RawMutex, I2C: I2cX, PINS> ErrorType for I2cDevice<'_, M, I2C, PINS> {
+    type Error = Error;
+}
+
+impl<M: RawMutex, I2C: I2cX, PINS> embedded_hal_async::i2c::I2c for I2cDevice<'_, M, I2C, PINS> {
+    async fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut bus = self.bus.lock().await;
+        bus.set_prescaler(self.speed.0, self.speed.1);
+        bus.transaction(address, operations).await
+    }
+}
@@ -0,0 +1,123 @@
+use super::{FLAG_READ, FLAG_WRITE};
+use crate::{
+    asynch::delay::Delay,
+    i2c::{duration_ticks, ten_bit_header, Error, I2c, I2cX},
+};
+use e310x::Clint;
+use embedded_hal::i2c::{NoAcknowledgeSource, Operation, TenBitAddress};
+use embedded_hal_async::i2c;
+
+impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
+    /// Asynchronously sends the full 10-bit address in write mode, after a (repeated) START condition.
+    async fn address_ten_bit_async(&mut self, address: TenBitAddress) -> Result<(), Error> {
+        self.write_txr(ten_bit_header(address) + FLAG_WRITE);
+        self.trigger_write(true, false);
+        self.wait_for_write_async(NoAcknowledgeSource::Address)
+            .await?;
+        self.write_txr(address as u8);
+        self.trigger_write(false, false);
+        self.wait_for_write_async(NoAcknowledgeSource::Address)
+            .await
+    }
+
+    /// Performs a 10-bit address transaction without timeout.
+    async fn transaction_ten_bit_async(
+        &mut self,
+        address: TenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Error> {
+        let n_ops = operations.len();
+        if n_ops == 0 {
+            return Ok(());
+        }
+
+        self.wait_idle_async().await;
+        self.reset();
+
+        // `None` before the first operation, `Some(true)` after a read
+        let mut last_op_was_read = None;
+
+        for i in 0..n_ops {
+            // The last byte of adjacent reads is not acknowledged
+            let next_op_is_read = matches!(operations.get(i + 1), Some(Operation::Read(_)));
+            match &mut operations[i] {
+                Operation::Write(bytes) => {
+                    // Adjacent writes are merged
+                    if last_op_was_read != Some(false) {
+                        self.address_ten_bit_async(address).await?;
+                    }
+                    last_op_was_read = Some(false);
+
+                    // Write bytes
+                    let n_bytes = bytes.len();
+                    for (j, byte) in bytes.iter().enumerate() {
+                        self.write_txr(*byte);
+                        self.trigger_write(false, (i == n_ops - 1) && (j == n_bytes - 1));
+                        self.wait_for_write_async(NoAcknowledgeSource::Data).await?;
+                    }
+                }
+                Operation::Read(buffer) => {
+                    // Adjacent reads are merged
+                    if last_op_was_read != Some(true) {
+                        // The device must be addressed in write mode before the read header
+                        if last_op_was_read.is_none() {
+                            self.address_ten_bit_async(address).await?;
+                        }
+                        // Repeated START with the header in read mode
+                        self.write_txr(ten_bit_header(address) + FLAG_READ);
+                        self.trigger_write(true, false);
+                        self.wait_for_write_async(NoAcknowledgeSource::Address)
+                            .await?;
+                    }
+                    last_op_was_read = Some(true);
+
+                    // Read bytes
+                    let n_bytes = buffer.len();
+                    for (j, byte) in buffer.iter_mut().enumerate() {
+                        let last_byte = j == n_bytes - 1;
+                        self.trigger_read(
+                            last_byte && !next_op_is_read,
+                            (i == n_ops - 1) && last_byte,
+                        );
+                        self.wait_for_read_async().await?;
+                        *byte = self.read_rxr();
+                    }
+                }
+            }
+        }
+        // The STOP condition is sent with the last byte, unless there is none
+        let last_op_is_empty = match &operations[n_ops - 1] {
+            Operation::Write(bytes) => bytes.is_empty(),
+            Operation::Read(buffer) => buffer.is_empty(),
+        };
+        if last_op_is_empty {
+            self.set_stop();
+        }
+        self.wait_idle_async().await;
+        Ok(())
+    }
+}
+
+impl<I2C: I2cX, PINS> i2c::I2c<TenBitAddress> for I2c<I2C, PINS> {
+    async fn transaction(
+        &mut self,
+        address: TenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let Some(timeout) = self.timeout else {
+            return self.transaction_ten_bit_async(address, operations).await;
+        };
+        let mut delay = Delay::new(unsafe { Clint::steal() }.mtimer());
+        let transaction = self.transaction_ten_bit_async(address, operations);
+        match delay
+            .with_timeout(duration_ticks(timeout), transaction)
+            .await
+        {
+            Some(result) => result,
+            None => {
+                self.abort_async();
+                Err(Error::Timeout)
+            }
+        }
+    }
+}
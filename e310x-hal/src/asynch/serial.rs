@@ -16,22 +16,144 @@
 //! - RX: Pin 23 IOF0
 //! - Interrupt::UART1
 
-use crate::serial::{Rx, RxPin, Serial, Tx, TxPin, UartX, WatermarkValue};
+use crate::asynch::delay::{Delay, TimerSlot};
+use crate::gpio::PinIndex;
+use crate::serial::{
+    on_buffered_irq, on_flow_control_irq, BufferedSerial, CommType, DriverGuard, FlowControlSerial,
+    Rs485, Rx, RxPin, Serial, Tx, TxPin, UartX, WatermarkValue, BUFFERED_STATE, FLOW_CONTROL_STATE,
+};
+use crate::time::Bps;
 use core::cell::RefCell;
 use core::future::poll_fn;
 use core::task::{Poll, Waker};
+use core::time::Duration;
 use critical_section::Mutex;
-use e310x::{Uart0, Uart1};
+use e310x::{Clint, Gpio0, Uart0, Uart1};
+use embedded_hal::digital::{InputPin, OutputPin};
 use embedded_hal_nb::serial;
+use portable_atomic::{AtomicU32, Ordering};
+use riscv::register::mcycle;
+use riscv_peripheral::aclint::mtimer::MTIMER;
 
 type WakerPair = (Option<Waker>, Option<Waker>);
 const N_UARTS: usize = 2;
 static UART_WAKERS: Mutex<RefCell<[WakerPair; N_UARTS]>> =
     Mutex::new(RefCell::new([const { (None, None) }; N_UARTS]));
+static BUFFERED_WAKERS: Mutex<RefCell<[WakerPair; N_UARTS]>> =
+    Mutex::new(RefCell::new([const { (None, None) }; N_UARTS]));
+
+/// Depth of the UART FIFOs
+const FIFO_DEPTH: usize = 8;
+/// RX watermark to set once the first byte of a bulk read arrives (0 if no bulk read is pending)
+static RX_IDLE_WATERMARK: [AtomicU32; N_UARTS] = [const { AtomicU32::new(0) }; N_UARTS];
+/// Idle line window of the pending bulk read in `MTIME` ticks
+static RX_IDLE_TICKS: [AtomicU32; N_UARTS] = [const { AtomicU32::new(0) }; N_UARTS];
+/// Idle line timer of the pending bulk read, removed when the read completes or is cancelled
+static RX_IDLE_TIMERS: Mutex<RefCell<[TimerSlot; N_UARTS]>> =
+    Mutex::new(RefCell::new([const { TimerSlot::new() }; N_UARTS]));
+
+/// Arms the timer that wakes the pending read after the idle line window.
+///
+/// Returns false if there is no pending read or the timer queue is full.
+/// In that case, the interrupt handler wakes the pending read so that it polls the line instead.
+fn arm_idle_timer<UART: UartX>() -> bool {
+    let clint = unsafe { Clint::steal() };
+    let mtimer = clint.mtimer();
+    let expires =
+        mtimer.mtime().read() + RX_IDLE_TICKS[UART::UART_INDEX].load(Ordering::Acquire) as u64;
+    let waker = critical_section::with(|cs| UART_WAKERS.borrow_ref(cs)[UART::UART_INDEX].0.clone());
+    waker.is_some_and(|waker| rearm_idle_timer::<UART>(&mtimer, expires, &waker))
+}
+
+/// Arms the idle line timer of the pending read at `expires`, replacing the previous deadline.
+///
+/// Returns false if the timer queue is full.
+fn rearm_idle_timer<UART: UartX>(mtimer: &MTIMER<Clint>, expires: u64, waker: &Waker) -> bool {
+    critical_section::with(|cs| {
+        RX_IDLE_TIMERS.borrow_ref_mut(cs)[UART::UART_INDEX].arm(mtimer, expires, waker)
+    })
+}
+
+/// Returns the TX watermark that signals room for `len` bytes in the TX FIFO (up to its depth).
+fn tx_watermark(len: usize) -> WatermarkValue {
+    // The watermark is pending when the FIFO holds less than `counter` bytes
+    let watermark = (FIFO_DEPTH + 1 - len.min(FIFO_DEPTH)).min(7) as u8;
+    WatermarkValue::try_from(watermark).unwrap()
+}
+
+/// Services the GPIO interrupt of a pin used as CTS line by a [`FlowControlSerial`].
+///
+/// Returns false if the pin is not used as CTS line.
+pub(crate) fn on_cts_irq(pin_n: usize) -> bool {
+    let Some(uart_index) = FLOW_CONTROL_STATE
+        .iter()
+        .position(|state| state.cts.load(Ordering::Acquire) == pin_n)
+    else {
+        return false;
+    };
+    let gpio = unsafe { Gpio0::steal() };
+    let pin_mask = 1 << pin_n;
+    // The peer is ready again, stop listening until the next write waits for CTS
+    unsafe {
+        gpio.fall_ie().modify(|r, w| w.bits(r.bits() & !pin_mask));
+        gpio.fall_ip().write(|w| w.bits(pin_mask));
+    }
+    critical_section::with(|cs| {
+        let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
+        if let Some(waker) = uartwaker[uart_index].1.take() {
+            waker.wake();
+        }
+    });
+    true
+}
 
 fn on_irq<UART: UartX>(uart: &UART) {
+    // A buffered serial interface owns the UART interrupts while it is active
+    if let Some(events) = on_buffered_irq(uart) {
+        critical_section::with(|cs| {
+            let mut uartwaker = BUFFERED_WAKERS.borrow_ref_mut(cs);
+            if events.rx {
+                if let Some(waker) = uartwaker[UART::UART_INDEX].0.take() {
+                    waker.wake();
+                }
+            }
+            if events.tx {
+                if let Some(waker) = uartwaker[UART::UART_INDEX].1.take() {
+                    waker.wake();
+                }
+            }
+        });
+        // A buffered transmitter leaves the RX interrupt to the async reader
+        if BUFFERED_STATE[UART::UART_INDEX].rx.is_available() {
+            return;
+        }
+    }
+    // Flow control owns the RX interrupt while it is active
+    let flow_control = on_flow_control_irq(uart);
+    if flow_control == Some(true) {
+        critical_section::with(|cs| {
+            let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
+            if let Some(waker) = uartwaker[UART::UART_INDEX].0.take() {
+                waker.wake();
+            }
+        });
+    }
     //Check if Rx interrupt is enabled
-    if uart.ie().read().rxwm().bit_is_set() && uart.ip().read().rxwm().bit_is_set() {
+    if flow_control.is_none()
+        && uart.ie().read().rxwm().bit_is_set()
+        && uart.ip().read().rxwm().bit_is_set()
+    {
+        // First byte of a bulk read: wait for the rest of the data or for an idle line
+        let watermark = RX_IDLE_WATERMARK[UART::UART_INDEX].swap(0, Ordering::AcqRel);
+        if watermark != 0 && uart.rxctrl().read().counter().bits() == 0 {
+            uart.rxctrl().modify(|r, w| {
+                w.enable().bit(r.enable().bit());
+                unsafe { w.counter().bits(watermark as u8) }
+            });
+            if arm_idle_timer::<UART>() {
+                return;
+            }
+        }
         // Wake the waker if it exists
         critical_section::with(|cs| {
             let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
@@ -46,7 +168,10 @@ fn on_irq<UART: UartX>(uart: &UART) {
         });
     }
     //Check if Tx interrupt is enabled
-    if uart.ie().read().txwm().bit_is_set() && uart.ip().read().txwm().bit_is_set() {
+    if !BUFFERED_STATE[UART::UART_INDEX].tx.is_available()
+        && uart.ie().read().txwm().bit_is_set()
+        && uart.ip().read().txwm().bit_is_set()
+    {
         // Wake the waker if it exists
         critical_section::with(|cs| {
             let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
@@ -62,40 +187,95 @@ fn on_irq<UART: UartX>(uart: &UART) {
     }
 }
 
-impl<UART: UartX, PIN: RxPin<UART>> embedded_io_async::Read for Rx<UART, PIN> {
-    /// This implementation is not side-effect free on cancel
-    #[inline]
-    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
-        if buf.is_empty() {
-            return Ok(0);
+/// Restores the UART interrupt enable bits and clears the waker slots when dropped.
+///
+/// Async UART operations hold a guard while pending, so that cancelling them
+/// does not leave interrupts enabled nor stale wakers behind.
+struct CancelGuard<UART: UartX> {
+    uart: UART,
+    comm_type: CommType,
+    /// Interrupt enable bits to restore, if any
+    ie: Option<(bool, bool)>,
+}
+
+impl<UART: UartX> CancelGuard<UART> {
+    /// Captures the current interrupt enable state of the UART.
+    fn new(comm_type: CommType) -> Self {
+        // SAFETY: the guard only restores the `ie` bits and the wakers of the owner operation
+        let uart = unsafe { UART::steal() };
+        let ie = uart.ie().read();
+        let ie = Some((ie.rxwm().bit(), ie.txwm().bit()));
+        Self {
+            uart,
+            comm_type,
+            ie,
         }
-        let mut count = 0;
+    }
 
-        poll_fn(|cx| {
-            let read = serial::Read::read(self);
-            match read {
-                Ok(b) => {
-                    buf[0] = b;
-                    count += 1;
-                    Poll::Ready(())
-                }
-                Err(nb::Error::WouldBlock) => {
-                    // Register the waker for the UART
-                    critical_section::with(|cs| {
-                        let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
-                        uartwaker[UART::UART_INDEX].0 = Some(cx.waker().clone());
-                    });
-                    //Enable interrupt for the UART
-                    self.set_watermark(WatermarkValue::W0);
-                    self.enable_interrupt();
-                    Poll::Pending
+    /// Only clears the waker slots, for operations that manage the interrupt enable bits on their own.
+    fn wakers_only(comm_type: CommType) -> Self {
+        let uart = unsafe { UART::steal() };
+        Self {
+            uart,
+            comm_type,
+            ie: None,
+        }
+    }
+}
+
+impl<UART: UartX> Drop for CancelGuard<UART> {
+    fn drop(&mut self) {
+        let (rx, tx) = match self.comm_type {
+            CommType::Rx => (true, false),
+            CommType::Tx => (false, true),
+            CommType::TxRx => (true, true),
+        };
+        // Done within the critical section so the interrupt handler sees a consistent state
+        critical_section::with(|cs| {
+            let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
+            if rx {
+                uartwaker[UART::UART_INDEX].0 = None;
+                RX_IDLE_WATERMARK[UART::UART_INDEX].store(0, Ordering::Release);
+                RX_IDLE_TIMERS.borrow_ref_mut(cs)[UART::UART_INDEX].disarm();
+            }
+            if tx {
+                uartwaker[UART::UART_INDEX].1 = None;
+            }
+            if let Some((rxwm, txwm)) = self.ie {
+                self.uart.ie().modify(|r, w| {
+                    w.rxwm().bit(if rx { rxwm } else { r.rxwm().bit() });
+                    w.txwm().bit(if tx { txwm } else { r.txwm().bit() })
+                });
+            }
+        });
+    }
+}
+
+impl<UART: UartX, PIN: RxPin<UART>> Rx<UART, PIN> {
+    /// Returns the number of bytes in the RX FIFO.
+    ///
+    /// The FE310 UART has no FIFO level register, so the level is probed with the watermark.
+    fn fifo_level(&mut self) -> usize {
+        critical_section::with(|_| {
+            let watermark = self.get_watermark();
+            let mut level = 0;
+            // The watermark is pending while the FIFO holds more than `counter` bytes
+            while level < FIFO_DEPTH {
+                self.set_watermark(WatermarkValue::try_from(level as u8).unwrap());
+                if !self.is_interrupt_pending() {
+                    break;
                 }
-                _ => unreachable!(),
+                level += 1;
             }
+            self.set_watermark(WatermarkValue::try_from(watermark).unwrap());
+            level
         })
-        .await;
+    }
 
-        for byte in buf.iter_mut().skip(1) {
+    /// Moves the contents of the RX FIFO to `buf`, returning the number of bytes read.
+    fn drain(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        for byte in buf.iter_mut() {
             match serial::Read::read(self) {
                 Ok(b) => {
                     *byte = b;
@@ -105,63 +285,244 @@ impl<UART: UartX, PIN: RxPin<UART>> embedded_io_async::Read for Rx<UART, PIN> {
                 _ => unreachable!(),
             }
         }
+        count
+    }
+
+    /// Returns the number of `MTIME` ticks of two frames at the current baud rate.
+    fn frame_idle_ticks(mtime_freq: usize, baud_rate: Bps) -> u64 {
+        // 10 bits per frame, rounded up to at least one tick
+        let bits = 2 * 10;
+        ((bits * mtime_freq as u64).div_ceil(baud_rate.0 as u64)).max(1)
+    }
+
+    /// Returns the number of `MTIME` ticks of a duration, rounded up to at least one tick.
+    fn duration_ticks(mtime_freq: usize, duration: Duration) -> u64 {
+        let ticks = (duration.as_nanos() * mtime_freq as u128).div_ceil(1_000_000_000);
+        (ticks as u64).max(1)
+    }
+
+    /// Waits until `buf` can be filled from the RX FIFO or the line has been idle for `idle_ticks`.
+    ///
+    /// The RX watermark is sized to the remaining buffer length (up to the FIFO depth),
+    /// so the task is woken once per FIFO worth of data instead of once per byte.
+    /// Bytes are only taken from the RX FIFO in the poll that completes the future.
+    ///
+    /// If `started` is false, the idle window only starts after receiving the first byte.
+    /// Otherwise, it starts right away, as the line is already active.
+    ///
+    /// Returns the number of bytes read and whether the read ended due to an idle line.
+    async fn read_bulk(&mut self, buf: &mut [u8], idle_ticks: u64, started: bool) -> (usize, bool) {
+        let _guard = CancelGuard::<UART>::new(CommType::Rx);
+        let target = buf.len().min(FIFO_DEPTH);
+        let clint = unsafe { Clint::steal() };
+        let mtimer = clint.mtimer();
+        let mtime = mtimer.mtime();
+        // FIFO level at the last idle check and the end of the current idle window
+        let mut last_level = if started { None } else { Some(0) };
+        let mut deadline = None;
+        // Whether the idle window is polled because the timer queue is full
+        let mut polling = false;
+
+        // Saturate rather than truncate windows longer than `u32::MAX` ticks (more than a day)
+        let stored_ticks = u32::try_from(idle_ticks).unwrap_or(u32::MAX);
+        let idle_ticks = u64::from(stored_ticks);
+        RX_IDLE_TICKS[UART::UART_INDEX].store(stored_ticks, Ordering::Release);
+        poll_fn(|cx| {
+            let level = self.fifo_level();
+            if level >= target {
+                return Poll::Ready((self.drain(buf), false));
+            }
+
+            // Register the waker for the UART
+            critical_section::with(|cs| {
+                let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
+                uartwaker[UART::UART_INDEX].0 = Some(cx.waker().clone());
+            });
+
+            if level == 0 && !started {
+                // Wait for the first byte, then the interrupt handler arms the idle timer
+                self.set_watermark(WatermarkValue::W0);
+                RX_IDLE_WATERMARK[UART::UART_INDEX].store(target as u32 - 1, Ordering::Release);
+                self.enable_interrupt();
+                return Poll::Pending;
+            }
+
+            let now = mtime.read();
+            if deadline.is_some_and(|deadline| now < deadline) {
+                // Early wake-up, the idle timer is still pending
+                if polling {
+                    cx.waker().wake_by_ref();
+                }
+                return Poll::Pending;
+            }
+            if last_level == Some(level) {
+                // No byte arrived during the whole idle window
+                return Poll::Ready((self.drain(buf), true));
+            }
+
+            last_level = Some(level);
+            let expires = now + idle_ticks;
+            // A single idle timer per read, moved to the end of the new idle window
+            polling = !rearm_idle_timer::<UART>(&mtimer, expires, cx.waker());
+            if polling {
+                // The timer queue is full, poll the line until the window ends
+                cx.waker().wake_by_ref();
+            }
+            deadline = Some(expires);
+            self.set_watermark(WatermarkValue::try_from(target as u8 - 1).unwrap());
+            self.enable_interrupt();
+            Poll::Pending
+        })
+        .await
+    }
+
+    /// Reads a frame delimited by line silence, as in Modbus RTU.
+    ///
+    /// Waits for the first byte and returns once no byte has arrived for `idle`,
+    /// or when `buf` is full. Returns the number of bytes read.
+    ///
+    /// # Note
+    ///
+    /// The FE310 UART has no idle line detection, so the line is sampled with the
+    /// timer queue of [`Delay`]. The gap is detected with `MTIME` resolution,
+    /// and it may take up to twice `idle` to detect it.
+    ///
+    /// This method is not cancel-safe: the bytes already read are lost if the future is dropped.
+    pub async fn read_until_idle(
+        &mut self,
+        buf: &mut [u8],
+        idle: Duration,
+    ) -> Result<usize, embedded_io::ErrorKind> {
+        let mtime_freq = unsafe { Clint::steal() }.mtimer().mtime_freq();
+        let idle_ticks = Self::duration_ticks(mtime_freq, idle);
+
+        let mut count = 0;
+        while count < buf.len() {
+            let (n, idle) = self
+                .read_bulk(&mut buf[count..], idle_ticks, count > 0)
+                .await;
+            count += n;
+            if idle {
+                break;
+            }
+        }
         Ok(count)
     }
+
+    /// Reads exactly `buf.len()` bytes, failing if they do not arrive within `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`embedded_io::ErrorKind::TimedOut`] if the timeout expires.
+    /// In that case, the number of bytes already read into `buf` is unspecified.
+    ///
+    /// This method is not cancel-safe: the bytes already read are lost if the future is dropped.
+    pub async fn read_exact_timeout(
+        &mut self,
+        buf: &mut [u8],
+        timeout: Duration,
+    ) -> Result<(), embedded_io::ErrorKind> {
+        let clint = unsafe { Clint::steal() };
+        let mtime_freq = clint.mtimer().mtime_freq();
+        let timeout_ticks = Self::duration_ticks(mtime_freq, timeout);
+        let idle_ticks = Self::frame_idle_ticks(mtime_freq, self.baud_rate());
+
+        let read = async {
+            let mut count = 0;
+            while count < buf.len() {
+                count += self.read_bulk(&mut buf[count..], idle_ticks, false).await.0;
+            }
+        };
+        let mut delay = Delay::new(clint.mtimer());
+        delay
+            .with_timeout(timeout_ticks, read)
+            .await
+            .ok_or(embedded_io::ErrorKind::TimedOut)
+    }
+}
+
+impl<UART: UartX, PIN: RxPin<UART>> embedded_io_async::Read for Rx<UART, PIN> {
+    /// This implementation is side-effect free on cancel.
+    ///
+    /// The read completes once `buf` can be filled from the RX FIFO (up to its depth),
+    /// or when no byte has arrived for two frame times after receiving some data.
+    /// Bytes are only taken from the RX FIFO in the same poll that completes the future,
+    /// so a cancelled read never consumes received data.
+    #[inline]
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mtime_freq = unsafe { Clint::steal() }.mtimer().mtime_freq();
+        let idle_ticks = Self::frame_idle_ticks(mtime_freq, self.baud_rate());
+        Ok(self.read_bulk(buf, idle_ticks, false).await.0)
+    }
 }
 
 impl<UART: UartX, PIN: TxPin<UART>> embedded_io_async::Write for Tx<UART, PIN> {
-    /// This implementation is not side-effect free on cancel
+    /// This implementation is side-effect free on cancel.
+    ///
+    /// The TX FIFO is filled in bulk, and the TX watermark is sized so that the task is
+    /// only woken when the FIFO has room for the remaining buffer (up to its depth).
+    /// Bytes are only pushed to the TX FIFO in the same poll that completes the future,
+    /// so a cancelled write never sends data.
     #[inline]
     async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
         if buf.is_empty() {
             return Ok(0);
         }
-        let mut count = 0;
+        let _guard = CancelGuard::<UART>::new(CommType::Tx);
+        let target = buf.len().min(FIFO_DEPTH);
 
-        poll_fn(|cx| match serial::Write::write(self, buf[0]) {
-            Ok(()) => {
-                count += 1;
-                Poll::Ready(())
+        poll_fn(|cx| {
+            let mut count = 0;
+            for byte in buf.iter() {
+                match serial::Write::write(self, *byte) {
+                    Ok(()) => count += 1,
+                    Err(nb::Error::WouldBlock) => break,
+                    _ => unreachable!(),
+                }
             }
-            Err(nb::Error::WouldBlock) => {
-                // Register the waker for the UART
-                critical_section::with(|cs| {
-                    let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
-                    uartwaker[UART::UART_INDEX].1 = Some(cx.waker().clone());
-                });
-                //Enable interrupt for the UART
-                self.set_watermark(WatermarkValue::W7);
-                self.enable_interrupt();
-                Poll::Pending
+            if count > 0 {
+                return Poll::Ready(Ok(count));
             }
-            _ => unreachable!(),
-        })
-        .await;
 
-        for byte in buf.iter().skip(1) {
-            match serial::Write::write(self, *byte) {
-                Ok(()) => count += 1,
-                Err(nb::Error::WouldBlock) => break,
-                _ => unreachable!(),
-            }
-        }
-        Ok(count)
+            // Register the waker for the UART
+            critical_section::with(|cs| {
+                let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
+                uartwaker[UART::UART_INDEX].1 = Some(cx.waker().clone());
+            });
+            self.set_watermark(tx_watermark(target));
+            self.enable_interrupt();
+            Poll::Pending
+        })
+        .await
     }
 
+    /// This implementation is side-effect free on cancel
     async fn flush(&mut self) -> Result<(), Self::Error> {
-        let mut flushed = false;
+        // The TX watermark is pending once the FIFO is empty
+        self.wait_watermark(WatermarkValue::W1).await;
+        Ok(())
+    }
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> Tx<UART, PIN> {
+    /// Waits until the TX FIFO holds less than `watermark` bytes.
+    async fn wait_watermark(&mut self, watermark: WatermarkValue) {
+        let _guard = CancelGuard::<UART>::new(CommType::Tx);
+
+        self.set_watermark(watermark);
         poll_fn(|cx| {
-            if flushed {
-                Poll::Ready(Ok(()))
+            if self.is_interrupt_pending() {
+                Poll::Ready(())
             } else {
-                flushed = true;
                 // Register the waker for the UART
                 critical_section::with(|cs| {
                     let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
                     uartwaker[UART::UART_INDEX].1 = Some(cx.waker().clone());
                 });
                 //Enable interrupt for the UART
-                self.set_watermark(WatermarkValue::W1);
                 self.enable_interrupt();
                 Poll::Pending
             }
@@ -186,6 +547,224 @@ impl<UART: UartX, TX: TxPin<UART>, RX> embedded_io_async::Write for Serial<UART,
     }
 }
 
+impl<UART, TX, RX, RTS, CTS> embedded_io_async::Read for FlowControlSerial<UART, TX, RX, RTS, CTS>
+where
+    UART: UartX,
+    TX: TxPin<UART>,
+    RX: RxPin<UART>,
+    RTS: OutputPin + PinIndex,
+    CTS: InputPin + PinIndex,
+{
+    /// This implementation is side-effect free on cancel
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // The RX interrupt keeps monitoring the RTS threshold after the read
+        let _guard = CancelGuard::<UART>::wakers_only(CommType::Rx);
+
+        poll_fn(|cx| {
+            // Register the waker before checking the FIFO to avoid missing a wake-up
+            critical_section::with(|cs| {
+                let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
+                uartwaker[UART::UART_INDEX].0 = Some(cx.waker().clone());
+            });
+            let count = self.drain_rx(buf);
+            self.resume_rx();
+            match count {
+                0 => {
+                    // Wake on the first byte, the interrupt handler restores the threshold
+                    self.serial.rx.set_watermark(WatermarkValue::W0);
+                    Poll::Pending
+                }
+                count => Poll::Ready(Ok(count)),
+            }
+        })
+        .await
+    }
+}
+
+impl<UART, TX, RX, RTS, CTS> embedded_io_async::Write for FlowControlSerial<UART, TX, RX, RTS, CTS>
+where
+    UART: UartX,
+    TX: TxPin<UART>,
+    RX: RxPin<UART>,
+    RTS: OutputPin + PinIndex,
+    CTS: InputPin + PinIndex,
+{
+    /// This implementation is side-effect free on cancel
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let _guard = CancelGuard::<UART>::new(CommType::Tx);
+        let gpio = unsafe { Gpio0::steal() };
+        let cts_mask = 1 << CTS::INDEX;
+
+        poll_fn(|cx| {
+            // Register the waker before checking CTS to avoid missing a wake-up
+            critical_section::with(|cs| {
+                let mut uartwaker = UART_WAKERS.borrow_ref_mut(cs);
+                uartwaker[UART::UART_INDEX].1 = Some(cx.waker().clone());
+            });
+            // CTS is checked before each TX FIFO refill
+            if !self.is_clear_to_send() {
+                // Resume on the falling edge of CTS
+                unsafe {
+                    gpio.fall_ip().write(|w| w.bits(cts_mask));
+                    gpio.fall_ie().modify(|r, w| w.bits(r.bits() | cts_mask));
+                }
+                if !self.is_clear_to_send() {
+                    return Poll::Pending;
+                }
+                unsafe { gpio.fall_ie().modify(|r, w| w.bits(r.bits() & !cts_mask)) };
+            }
+            match self.fill_tx(buf) {
+                0 => {
+                    self.serial.tx.set_watermark(tx_watermark(buf.len()));
+                    self.serial.tx.enable_interrupt();
+                    Poll::Pending
+                }
+                count => Poll::Ready(Ok(count)),
+            }
+        })
+        .await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.serial.tx.flush().await
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, DE: OutputPin> embedded_io_async::Read
+    for Rs485<UART, TX, RX, DE>
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if !self.stash.is_empty() && !buf.is_empty() {
+            return Ok(self.stash.read(buf));
+        }
+        self.serial.rx.read(buf).await
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, DE: OutputPin> embedded_io_async::Write
+    for Rs485<UART, TX, RX, DE>
+{
+    /// If the future is dropped, the driver enable pin is released right away,
+    /// even if the bytes already in the TX FIFO are still being sent.
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let Self { serial, de, stash } = self;
+        serial.stash_rx(stash);
+        let driver = DriverGuard::enable(de)?;
+        let mut count = 0;
+        while count < buf.len() {
+            count += serial.tx.write(&buf[count..]).await?;
+            serial.discard_rx();
+        }
+        // Wait until the last byte moves to the shift register, then sleep for the
+        // whole ticks of its frame and busy wait only for the remainder
+        serial.tx.wait_watermark(WatermarkValue::W1).await;
+        let start = mcycle::read64();
+        let mtimer = unsafe { Clint::steal() }.mtimer();
+        let frame_ticks = serial.frame_ticks(mtimer.mtime_freq());
+        Delay::new(mtimer).delay_ticks(frame_ticks).await;
+        serial.finish_frame(start);
+        let res = driver.release();
+        // The echo of the last frame is received during its stop bit
+        serial.discard_rx();
+        res.map(|()| count)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        // The driver enable pin is only released once the transmission is complete
+        Ok(())
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io_async::Read
+    for BufferedSerial<UART, TX, RX>
+{
+    /// This implementation is side-effect free on cancel
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let rx = &Self::state().rx;
+
+        poll_fn(|cx| {
+            // Register the waker before checking the buffer to avoid missing a wake-up
+            critical_section::with(|cs| {
+                let mut uartwaker = BUFFERED_WAKERS.borrow_ref_mut(cs);
+                uartwaker[UART::UART_INDEX].0 = Some(cx.waker().clone());
+            });
+            match rx.pop_slice(buf) {
+                0 => Poll::Pending,
+                count => Poll::Ready(Ok(count)),
+            }
+        })
+        .await
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io_async::Write
+    for BufferedSerial<UART, TX, RX>
+{
+    /// This implementation is side-effect free on cancel
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        poll_fn(|cx| {
+            // Register the waker before checking the buffer to avoid missing a wake-up
+            critical_section::with(|cs| {
+                let mut uartwaker = BUFFERED_WAKERS.borrow_ref_mut(cs);
+                uartwaker[UART::UART_INDEX].1 = Some(cx.waker().clone());
+            });
+            match self.push_tx(buf) {
+                0 => Poll::Pending,
+                count => Poll::Ready(Ok(count)),
+            }
+        })
+        .await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        let tx = &Self::state().tx;
+
+        // Wait until the interrupt handler moves the whole ring buffer to the TX FIFO
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                let mut uartwaker = BUFFERED_WAKERS.borrow_ref_mut(cs);
+                uartwaker[UART::UART_INDEX].1 = Some(cx.waker().clone());
+            });
+            if tx.is_empty() {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await;
+
+        // At most one FIFO worth of data is left, the interrupt fires once it is sent
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                let mut uartwaker = BUFFERED_WAKERS.borrow_ref_mut(cs);
+                uartwaker[UART::UART_INDEX].1 = Some(cx.waker().clone());
+            });
+            if self.poll_tx_fifo_empty() {
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
 #[riscv_rt::external_interrupt(e310x::interrupt::ExternalInterrupt::UART0)]
 fn uart0_interrupt_handler() {
     let uart0 = unsafe { Uart0::steal() };
@@ -0,0 +1,301 @@
+//! Exception handler with register dump and backtrace
+//!
+//! With the `exception-handler` feature, this module provides the `ExceptionHandler` called by
+//! `riscv-rt` for the exceptions without a specific handler (e.g., load access faults or
+//! illegal instructions). The handler:
+//!
+//! 1. Captures an [`ExceptionInfo`] with the decoded `mcause`, `mepc`, `mtval`, the saved
+//!    registers of the trap frame, and a [`Backtrace`].
+//! 2. Writes it to the [`core::fmt::Write`] registered with [`set_writer`], if any.
+//! 3. Calls the hook registered with [`set_hook`], if any (e.g., to store a crash record).
+//! 4. Halts the core with interrupts disabled.
+//!
+//! # Backtrace
+//!
+//! The backtrace starts at `mepc`, followed by the return addresses found by walking the
+//! frame-pointer chain of the interrupted code. It is only available if the application is
+//! built with frame pointers:
+//!
+//! ```toml
+//! [target.'cfg(all(target_arch = "riscv32", target_os = "none"))']
+//! rustflags = ["-C", "force-frame-pointers=yes"]
+//! ```
+//!
+//! Without frame pointers, the backtrace only contains `mepc`. The walk stops at the first
+//! frame pointer outside of the stack or return address outside of the `.text` section.
+//! The addresses can be resolved with `addr2line -e <elf> <address>`.
+//!
+//! # Example
+//!```ignore
+//! static mut STDOUT: Option<Stdout<...>> = None;
+//!
+//! e310x_hal::exception::set_writer(unsafe { (*addr_of_mut!(STDOUT)).insert(stdout) });
+//! e310x_hal::exception::set_hook(|info| store_crash_record(info.mepc, info.mcause));
+//!```
+
+use core::{
+    fmt::{self, Write},
+    ptr,
+};
+use e310x::interrupt::Exception;
+use riscv::{
+    interrupt::free,
+    register::{mcause, mepc, mtval},
+    ExceptionNumber,
+};
+use riscv_rt::TrapFrame;
+
+/// Maximum number of addresses in a [`Backtrace`]
+pub const MAX_FRAMES: usize = 16;
+
+/// Function called with the exception information before halting the core
+pub type ExceptionHook = fn(&ExceptionInfo);
+
+static mut WRITER: Option<&'static mut dyn fmt::Write> = None;
+
+static mut HOOK: Option<ExceptionHook> = None;
+
+/// Return addresses of the interrupted code, starting at `mepc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backtrace {
+    frames: [usize; MAX_FRAMES],
+    len: usize,
+}
+
+impl Backtrace {
+    /// Returns the addresses of the backtrace, innermost first.
+    pub fn frames(&self) -> &[usize] {
+        &self.frames[..self.len]
+    }
+
+    /// Walks the frame-pointer chain of the code interrupted by `trap_frame`.
+    fn capture(pc: usize, trap_frame: &TrapFrame) -> Self {
+        let mut backtrace = Self {
+            frames: [0; MAX_FRAMES],
+            len: 0,
+        };
+        backtrace.push(pc);
+
+        // `_start_trap_rust` is called with the trap frame at the top of its stack frame,
+        // so its frame pointer is the address of the trap frame. The frame that precedes it
+        // in the chain belongs to the interrupted code.
+        let trap_fp = trap_frame as *const TrapFrame as usize;
+        let mut fp = frame_pointer();
+        let mut found = false;
+        for _ in 0..2 * MAX_FRAMES {
+            if !is_stack_address(fp) {
+                break;
+            }
+            // SAFETY: `fp` points to a frame record within the stack
+            let (ra, next_fp) =
+                unsafe { (*(fp as *const usize).sub(1), *(fp as *const usize).sub(2)) };
+            if found {
+                if !is_text_address(ra) || !backtrace.push(ra) {
+                    break;
+                }
+            } else {
+                found = fp == trap_fp;
+            }
+            // The stack grows downwards, so callers have greater frame pointers
+            if next_fp <= fp {
+                break;
+            }
+            fp = next_fp;
+        }
+        backtrace
+    }
+
+    /// Appends an address, returning false if the backtrace is full.
+    fn push(&mut self, address: usize) -> bool {
+        if self.len == MAX_FRAMES {
+            return false;
+        }
+        self.frames[self.len] = address;
+        self.len += 1;
+        true
+    }
+}
+
+/// Information about an exception
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExceptionInfo {
+    /// Raw value of `mcause`
+    pub mcause: usize,
+    /// Address of the instruction that caused the exception
+    pub mepc: usize,
+    /// Faulting address or instruction, depending on the exception
+    pub mtval: usize,
+    /// Return address (`ra`) when the exception occurred
+    pub ra: usize,
+    /// Temporary registers `t0` to `t2` when the exception occurred
+    pub t: [usize; 3],
+    /// Argument registers `a0` to `a5` when the exception occurred
+    pub a: [usize; 6],
+    /// Backtrace of the interrupted code
+    pub backtrace: Backtrace,
+}
+
+impl ExceptionInfo {
+    /// Captures the exception information from the trap frame saved by `riscv-rt`.
+    pub fn capture(trap_frame: &TrapFrame) -> Self {
+        let mepc = mepc::read();
+        Self {
+            mcause: mcause::read().bits(),
+            mepc,
+            mtval: mtval::read(),
+            ra: trap_frame.ra,
+            t: [trap_frame.t0, trap_frame.t1, trap_frame.t2],
+            a: [
+                trap_frame.a0,
+                trap_frame.a1,
+                trap_frame.a2,
+                trap_frame.a3,
+                trap_frame.a4,
+                trap_frame.a5,
+            ],
+            backtrace: Backtrace::capture(mepc, trap_frame),
+        }
+    }
+
+    /// Returns the exception that occurred, or `None` if the cause is not a valid exception.
+    pub fn exception(&self) -> Option<Exception> {
+        Exception::from_number(self.mcause).ok()
+    }
+
+    /// Returns a readable description of the exception.
+    pub fn description(&self) -> &'static str {
+        match self.exception() {
+            Some(Exception::InstructionMisaligned) => "instruction address misaligned",
+            Some(Exception::InstructionFault) => "instruction access fault",
+            Some(Exception::IllegalInstruction) => "illegal instruction",
+            Some(Exception::Breakpoint) => "breakpoint",
+            Some(Exception::LoadMisaligned) => "load address misaligned",
+            Some(Exception::LoadFault) => "load access fault",
+            Some(Exception::StoreMisaligned) => "store/AMO address misaligned",
+            Some(Exception::StoreFault) => "store/AMO access fault",
+            Some(Exception::UserEnvCall) => "environment call from U-mode",
+            Some(Exception::SupervisorEnvCall) => "environment call from S-mode",
+            Some(Exception::MachineEnvCall) => "environment call from M-mode",
+            Some(Exception::InstructionPageFault) => "instruction page fault",
+            Some(Exception::LoadPageFault) => "load page fault",
+            Some(Exception::StorePageFault) => "store/AMO page fault",
+            None => "unknown exception",
+        }
+    }
+}
+
+impl fmt::Display for ExceptionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "exception: {} (mcause = {:#x})",
+            self.description(),
+            self.mcause
+        )?;
+        writeln!(
+            f,
+            "  mepc = {:#010x}  mtval = {:#010x}",
+            self.mepc, self.mtval
+        )?;
+        writeln!(
+            f,
+            "  ra = {:#010x}  t0 = {:#010x}  t1 = {:#010x}  t2 = {:#010x}",
+            self.ra, self.t[0], self.t[1], self.t[2]
+        )?;
+        for (i, pair) in self.a.chunks(2).enumerate() {
+            writeln!(
+                f,
+                "  a{} = {:#010x}  a{} = {:#010x}",
+                2 * i,
+                pair[0],
+                2 * i + 1,
+                pair[1]
+            )?;
+        }
+        writeln!(f, "backtrace:")?;
+        for (i, address) in self.backtrace.frames().iter().enumerate() {
+            writeln!(f, "  #{i:<2} {address:#010x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Registers the writer for the exception information.
+///
+/// The previous writer, if any, is dropped.
+pub fn set_writer(writer: &'static mut dyn fmt::Write) {
+    free(|| unsafe { &mut *ptr::addr_of_mut!(WRITER) }.replace(writer));
+}
+
+/// Registers a function called with the exception information before halting the core.
+pub fn set_hook(hook: ExceptionHook) {
+    free(|| unsafe { &mut *ptr::addr_of_mut!(HOOK) }.replace(hook));
+}
+
+/// Returns the current frame pointer (`s0`).
+#[cfg(target_arch = "riscv32")]
+#[inline(always)]
+fn frame_pointer() -> usize {
+    let fp: usize;
+    unsafe { core::arch::asm!("mv {}, s0", out(reg) fp) };
+    fp
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn frame_pointer() -> usize {
+    0
+}
+
+/// Returns true if `fp` may be a frame pointer.
+#[cfg(target_arch = "riscv32")]
+fn is_stack_address(fp: usize) -> bool {
+    extern "C" {
+        static __estack: u8;
+        static __sstack: u8;
+    }
+    let (bottom, top) = (
+        ptr::addr_of!(__estack) as usize,
+        ptr::addr_of!(__sstack) as usize,
+    );
+    fp % 4 == 0 && fp > bottom + 8 && fp <= top
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn is_stack_address(_fp: usize) -> bool {
+    false
+}
+
+/// Returns true if `ra` may be a return address.
+#[cfg(target_arch = "riscv32")]
+fn is_text_address(ra: usize) -> bool {
+    extern "C" {
+        static __stext: u8;
+        static __etext: u8;
+    }
+    let (start, end) = (
+        ptr::addr_of!(__stext) as usize,
+        ptr::addr_of!(__etext) as usize,
+    );
+    (start..end).contains(&ra)
+}
+
+#[cfg(not(target_arch = "riscv32"))]
+fn is_text_address(_ra: usize) -> bool {
+    false
+}
+
+#[export_name = "ExceptionHandler"]
+fn exception_handler(trap_frame: &TrapFrame) -> ! {
+    riscv::interrupt::disable();
+    let info = ExceptionInfo::capture(trap_frame);
+    // SAFETY: interrupts are disabled
+    if let Some(writer) = unsafe { &mut *ptr::addr_of_mut!(WRITER) } {
+        let _ = write!(writer, "{info}");
+    }
+    if let Some(hook) = unsafe { *ptr::addr_of!(HOOK) } {
+        hook(&info);
+    }
+    loop {
+        riscv::asm::wfi();
+    }
+}
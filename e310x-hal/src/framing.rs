@@ -0,0 +1,135 @@
+//! Packet framing over byte streams
+//!
+//! This module provides [COBS] and [SLIP] framing on top of any [`embedded_io`] reader or writer
+//! (e.g., [`serial::Tx`](crate::serial::Tx) and [`serial::Rx`](crate::serial::Rx)).
+//! With the `async` feature, [`embedded_io_async`] readers and writers are supported too.
+//!
+//! Every frame carries a CRC-16/CCITT-FALSE of its payload, appended in big-endian order
+//! before encoding. [`FrameReader`] decodes frames directly into a caller buffer, checks the
+//! CRC, and returns the payload length. Oversized and corrupted frames are discarded up to
+//! the next delimiter, so the reader resynchronizes on its own.
+//!
+//! The codecs are pure logic and do not depend on the target.
+//!
+//! # Example
+//!```ignore
+//! let (tx, rx) = serial.split();
+//! let mut writer = CobsWriter::new(tx);
+//! let mut reader = CobsReader::new(rx);
+//!
+//! writer.write_frame(b"hello")?;
+//! let mut buf = [0; 64];
+//! let len = reader.read_frame(&mut buf)?;
+//!```
+//!
+//! [COBS]: https://en.wikipedia.org/wiki/Consistent_Overhead_Byte_Stuffing
+//! [SLIP]: https://en.wikipedia.org/wiki/Serial_Line_Internet_Protocol
+
+mod cobs; // contains the COBS codec
+mod crc; // contains the CRC-16 computation
+mod io; // contains the frame readers and writers
+mod slip; // contains the SLIP codec
+
+pub use cobs::*;
+pub use crc::*;
+pub use io::*;
+pub use slip::*;
+
+/// Errors when decoding a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The decoded frame does not fit in the caller buffer
+    Oversized,
+    /// The frame encoding is invalid
+    Corrupted,
+}
+
+/// Errors when reading or writing frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError<E> {
+    /// Error of the underlying reader or writer
+    Io(E),
+    /// The decoded frame does not fit in the caller buffer
+    Oversized,
+    /// The frame encoding is invalid
+    Corrupted,
+    /// The frame is shorter than its CRC
+    TooShort,
+    /// The CRC of the frame does not match its payload
+    Crc,
+    /// The underlying reader reached the end of the stream
+    Eof,
+}
+
+impl<E> From<DecodeError> for FrameError<E> {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::Oversized => Self::Oversized,
+            DecodeError::Corrupted => Self::Corrupted,
+        }
+    }
+}
+
+impl<E: embedded_io::Error> embedded_io::Error for FrameError<E> {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        match self {
+            Self::Io(e) => e.kind(),
+            Self::Eof => embedded_io::ErrorKind::Other,
+            _ => embedded_io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+/// Streaming frame decoder
+pub trait Decoder: Default {
+    /// Feeds an encoded byte to the decoder, storing the decoded bytes in `buf`.
+    ///
+    /// The same buffer must be used until a frame is complete.
+    /// Returns the length of the decoded frame once its delimiter is received.
+    /// Empty frames (i.e., consecutive delimiters) are skipped.
+    fn feed(&mut self, byte: u8, buf: &mut [u8]) -> Option<Result<usize, DecodeError>>;
+}
+
+/// Framing codec
+pub trait Codec {
+    /// Iterator over the encoded bytes of a frame, including its delimiter
+    type Encoder<I: Iterator<Item = u8> + Clone>: Iterator<Item = u8>;
+    /// Streaming decoder
+    type Decoder: Decoder;
+
+    /// Returns an iterator over the encoded bytes of `data`.
+    fn encode<I: Iterator<Item = u8> + Clone>(data: I) -> Self::Encoder<I>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_io::{Error, ErrorKind};
+
+    #[test]
+    fn decode_errors_convert_to_frame_errors() {
+        assert_eq!(
+            FrameError::<ErrorKind>::from(DecodeError::Oversized),
+            FrameError::Oversized
+        );
+        assert_eq!(
+            FrameError::<ErrorKind>::from(DecodeError::Corrupted),
+            FrameError::Corrupted
+        );
+    }
+
+    #[test]
+    fn frame_error_kinds() {
+        let io = FrameError::Io(ErrorKind::TimedOut);
+        assert_eq!(io.kind(), ErrorKind::TimedOut);
+        assert_eq!(FrameError::<ErrorKind>::Eof.kind(), ErrorKind::Other);
+        for e in [
+            FrameError::<ErrorKind>::Oversized,
+            FrameError::Corrupted,
+            FrameError::TooShort,
+            FrameError::Crc,
+        ] {
+            assert_eq!(e.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use super::{Codec, DecodeError, Decoder};
+
+/// Maximum number of data bytes in a COBS group
+const MAX_GROUP: usize = 254;
+
+/// Consistent Overhead Byte Stuffing codec
+///
+/// Frames are delimited by a zero byte, which never appears in the encoded data.
+/// The overhead is one byte every 254 bytes of data.
+pub struct Cobs;
+
+impl Codec for Cobs {
+    type Encoder<I: Iterator<Item = u8> + Clone> = CobsEncoder<I>;
+    type Decoder = CobsDecoder;
+
+    fn encode<I: Iterator<Item = u8> + Clone>(data: I) -> Self::Encoder<I> {
+        CobsEncoder::new(data)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum EncoderState {
+    /// Next byte is the code of a group
+    Code,
+    /// Number of data bytes left in the current group, and whether the group is full
+    Data(usize, bool),
+    /// Next byte is the frame delimiter
+    Delimiter,
+    /// The frame is complete
+    Done,
+}
+
+/// Iterator over the COBS encoded bytes of a frame, including its delimiter
+#[derive(Clone)]
+pub struct CobsEncoder<I> {
+    data: I,
+    state: EncoderState,
+}
+
+impl<I: Iterator<Item = u8> + Clone> CobsEncoder<I> {
+    /// Creates a new encoder for the given data.
+    pub fn new(data: I) -> Self {
+        Self {
+            data,
+            state: EncoderState::Code,
+        }
+    }
+}
+
+impl<I: Iterator<Item = u8> + Clone> Iterator for CobsEncoder<I> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            match self.state {
+                EncoderState::Code => {
+                    // Look ahead for the next zero, up to a full group
+                    let run = self
+                        .data
+                        .clone()
+                        .take(MAX_GROUP)
+                        .take_while(|b| *b != 0)
+                        .count();
+                    self.state = EncoderState::Data(run, run == MAX_GROUP);
+                    return Some(run as u8 + 1);
+                }
+                EncoderState::Data(0, full) => {
+                    // Full groups are not followed by a zero, otherwise skip the zero
+                    self.state = match full || self.data.next().is_some() {
+                        true => EncoderState::Code,
+                        false => EncoderState::Delimiter,
+                    };
+                }
+                EncoderState::Data(n, full) => {
+                    self.state = EncoderState::Data(n - 1, full);
+                    return self.data.next();
+                }
+                EncoderState::Delimiter => {
+                    self.state = EncoderState::Done;
+                    return Some(0);
+                }
+                EncoderState::Done => return None,
+            }
+        }
+    }
+}
+
+/// Streaming COBS decoder
+#[derive(Default)]
+pub struct CobsDecoder {
+    /// Data bytes left in the current group (0 if the next byte is a code)
+    left: u8,
+    /// Whether the current group is followed by a zero
+    zero: bool,
+    len: usize,
+    in_frame: bool,
+    error: Option<DecodeError>,
+}
+
+impl CobsDecoder {
+    fn push(&mut self, byte: u8, buf: &mut [u8]) {
+        match buf.get_mut(self.len) {
+            Some(b) => {
+                *b = byte;
+                self.len += 1;
+            }
+            None => self.error = Some(DecodeError::Oversized),
+        }
+    }
+}
+
+impl Decoder for CobsDecoder {
+    fn feed(&mut self, byte: u8, buf: &mut [u8]) -> Option<Result<usize, DecodeError>> {
+        if byte == 0 {
+            let res = match (self.in_frame, self.error, self.left) {
+                (false, _, _) => None,
+                (true, Some(e), _) => Some(Err(e)),
+                (true, None, 0) => Some(Ok(self.len)),
+                // The frame ends within a group
+                (true, None, _) => Some(Err(DecodeError::Corrupted)),
+            };
+            *self = Self::default();
+            return res;
+        }
+
+        self.in_frame = true;
+        if self.error.is_some() {
+            // Skip the rest of the frame
+            return None;
+        }
+        if self.left == 0 {
+            if self.zero {
+                self.push(0, buf);
+            }
+            self.left = byte - 1;
+            self.zero = byte as usize != MAX_GROUP + 1;
+        } else {
+            self.push(byte, buf);
+            self.left -= 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes `data` into `out`, returning the encoded length.
+    fn encode(data: &[u8], out: &mut [u8]) -> usize {
+        let mut len = 0;
+        for (o, byte) in out.iter_mut().zip(Cobs::encode(data.iter().copied())) {
+            *o = byte;
+            len += 1;
+        }
+        assert!(len < out.len(), "output buffer too small");
+        len
+    }
+
+    /// Feeds `encoded` to a new decoder, returning the result of the first complete frame.
+    fn decode(encoded: &[u8], buf: &mut [u8]) -> Option<Result<usize, DecodeError>> {
+        let mut decoder = CobsDecoder::default();
+        encoded.iter().find_map(|byte| decoder.feed(*byte, buf))
+    }
+
+    fn round_trip(data: &[u8]) {
+        let mut encoded = [0; 600];
+        let len = encode(data, &mut encoded);
+        assert_eq!(encoded[len - 1], 0);
+        assert!(!encoded[..len - 1].contains(&0));
+
+        let mut buf = [0; 512];
+        assert_eq!(decode(&encoded[..len], &mut buf), Some(Ok(data.len())));
+        assert_eq!(&buf[..data.len()], data);
+    }
+
+    #[test]
+    fn encode_known_vectors() {
+        let mut encoded = [0; 16];
+        let len = encode(&[], &mut encoded);
+        assert_eq!(&encoded[..len], &[0x01, 0x00]);
+        let len = encode(&[0x00], &mut encoded);
+        assert_eq!(&encoded[..len], &[0x01, 0x01, 0x00]);
+        let len = encode(&[0x11, 0x22, 0x00, 0x33], &mut encoded);
+        assert_eq!(&encoded[..len], &[0x03, 0x11, 0x22, 0x02, 0x33, 0x00]);
+        let len = encode(&[0x11, 0x00, 0x00, 0x22], &mut encoded);
+        assert_eq!(&encoded[..len], &[0x02, 0x11, 0x01, 0x02, 0x22, 0x00]);
+    }
+
+    #[test]
+    fn round_trip_with_zeros() {
+        round_trip(&[]);
+        round_trip(&[0x00]);
+        round_trip(&[0x00, 0x00]);
+        round_trip(&[0x11, 0x00, 0x22, 0x00]);
+        round_trip(&[0x00, 0x11, 0x22, 0x33]);
+    }
+
+    #[test]
+    fn group_boundary() {
+        let mut data = [0; 512];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = (i % 255) as u8 + 1;
+        }
+        for len in [253, 254, 255, 508, 509] {
+            round_trip(&data[..len]);
+        }
+
+        // A full group has no implicit zero
+        let mut encoded = [0; 300];
+        let len = encode(&data[..254], &mut encoded);
+        assert_eq!(encoded[0], 0xff);
+        assert_eq!(&encoded[1..255], &data[..254]);
+        assert_eq!(&encoded[255..len], &[0x01, 0x00]);
+
+        // Zeros right before and after a full group
+        data[0] = 0;
+        data[255] = 0;
+        round_trip(&data[..256]);
+        round_trip(&data[..300]);
+    }
+
+    #[test]
+    fn decode_minimal_full_group() {
+        // Encoders may end a full group with the delimiter
+        let mut encoded = [0; 256];
+        encoded[0] = 0xff;
+        for (i, byte) in encoded[1..255].iter_mut().enumerate() {
+            *byte = i as u8 + 1;
+        }
+        let mut buf = [0; 256];
+        assert_eq!(decode(&encoded, &mut buf), Some(Ok(254)));
+        assert_eq!(&buf[..254], &encoded[1..255]);
+    }
+
+    #[test]
+    fn skip_empty_frames() {
+        let mut decoder = CobsDecoder::default();
+        let mut buf = [0; 4];
+        assert_eq!(decoder.feed(0x00, &mut buf), None);
+        assert_eq!(decoder.feed(0x00, &mut buf), None);
+        assert_eq!(decoder.feed(0x02, &mut buf), None);
+        assert_eq!(decoder.feed(0x11, &mut buf), None);
+        assert_eq!(decoder.feed(0x00, &mut buf), Some(Ok(1)));
+        assert_eq!(buf[0], 0x11);
+    }
+
+    #[test]
+    fn oversized_frame() {
+        let mut encoded = [0; 16];
+        let len = encode(&[1, 2, 3, 4, 5], &mut encoded);
+        let mut buf = [0; 4];
+        assert_eq!(
+            decode(&encoded[..len], &mut buf),
+            Some(Err(DecodeError::Oversized))
+        );
+
+        // The decoder resynchronizes on the next delimiter
+        let mut decoder = CobsDecoder::default();
+        let results = encoded[..len]
+            .iter()
+            .chain(&[0x03, 0x11, 0x22, 0x00])
+            .filter_map(|byte| decoder.feed(*byte, &mut buf));
+        assert!(results.eq([Err(DecodeError::Oversized), Ok(2)]));
+        assert_eq!(&buf[..2], &[0x11, 0x22]);
+    }
+
+    #[test]
+    fn truncated_frame() {
+        let mut buf = [0; 16];
+        // The delimiter is received before the end of the group
+        assert_eq!(
+            decode(&[0x04, 0x11, 0x22, 0x00], &mut buf),
+            Some(Err(DecodeError::Corrupted))
+        );
+        // Incomplete frames are not reported
+        assert_eq!(decode(&[0x03, 0x11, 0x22], &mut buf), None);
+    }
+}
@@ -0,0 +1,50 @@
+/// Nibble lookup table for the CRC-16/CCITT-FALSE polynomial (0x1021)
+const CRC16_TABLE: [u16; 16] = [
+    0x0000, 0x1021, 0x2042, 0x3063, 0x4084, 0x50a5, 0x60c6, 0x70e7, 0x8108, 0x9129, 0xa14a, 0xb16b,
+    0xc18c, 0xd1ad, 0xe1ce, 0xf1ef,
+];
+
+/// Initial value of the CRC-16/CCITT-FALSE computation
+pub const CRC16_INIT: u16 = 0xffff;
+
+/// Updates a CRC-16/CCITT-FALSE with the given data.
+pub fn crc16_update(mut crc: u16, data: &[u8]) -> u16 {
+    for byte in data {
+        crc = (crc << 4) ^ CRC16_TABLE[((crc >> 12) ^ (*byte as u16 >> 4)) as usize];
+        crc = (crc << 4) ^ CRC16_TABLE[((crc >> 12) ^ (*byte as u16 & 0x0f)) as usize];
+    }
+    crc
+}
+
+/// Computes the CRC-16/CCITT-FALSE of the given data.
+pub fn crc16(data: &[u8]) -> u16 {
+    crc16_update(CRC16_INIT, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29b1);
+        assert_eq!(crc16(&[]), CRC16_INIT);
+    }
+
+    #[test]
+    fn incremental_update() {
+        let crc = crc16_update(crc16(b"1234"), b"56789");
+        assert_eq!(crc, crc16(b"123456789"));
+    }
+
+    #[test]
+    fn detects_single_bit_errors() {
+        let data = *b"123456789";
+        let crc = crc16(&data);
+        for i in 0..data.len() * 8 {
+            let mut corrupted = data;
+            corrupted[i / 8] ^= 1 << (i % 8);
+            assert_ne!(crc16(&corrupted), crc);
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use super::{crc16, Cobs, Codec, Decoder, FrameError, Slip};
+use core::{
+    array,
+    iter::{Chain, Copied},
+    marker::PhantomData,
+    slice,
+};
+
+/// Number of encoded bytes written to the underlying writer at once
+const CHUNK_LEN: usize = 32;
+
+/// Size of the read-ahead buffer of frame readers
+const RAW_LEN: usize = 16;
+
+/// Bytes of a frame before encoding: the payload followed by its CRC
+type FrameBytes<'a> = Chain<Copied<slice::Iter<'a, u8>>, array::IntoIter<u8, 2>>;
+
+/// Fills `chunk` with encoded bytes, returning the number of bytes written.
+fn fill_chunk(chunk: &mut [u8], encoder: &mut impl Iterator<Item = u8>) -> usize {
+    chunk
+        .iter_mut()
+        .zip(encoder)
+        .map(|(c, byte)| *c = byte)
+        .count()
+}
+
+/// Writes frames to an [`embedded_io`] writer
+pub struct FrameWriter<W, C> {
+    writer: W,
+    _codec: PhantomData<C>,
+}
+
+/// Writes COBS frames to an [`embedded_io`] writer
+pub type CobsWriter<W> = FrameWriter<W, Cobs>;
+
+/// Writes SLIP frames to an [`embedded_io`] writer
+pub type SlipWriter<W> = FrameWriter<W, Slip>;
+
+impl<W, C: Codec> FrameWriter<W, C> {
+    /// Creates a new frame writer.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            _codec: PhantomData,
+        }
+    }
+
+    /// Releases the underlying writer
+    pub fn free(self) -> W {
+        self.writer
+    }
+
+    /// Returns an iterator over the encoded bytes of the frame, including its CRC.
+    fn encoder(payload: &[u8]) -> C::Encoder<FrameBytes<'_>> {
+        let crc = crc16(payload).to_be_bytes();
+        C::encode(payload.iter().copied().chain(crc))
+    }
+}
+
+impl<W: embedded_io::Write, C: Codec> FrameWriter<W, C> {
+    /// Encodes `payload` and its CRC, and writes the resulting frame.
+    pub fn write_frame(&mut self, payload: &[u8]) -> Result<(), FrameError<W::Error>> {
+        let mut encoder = Self::encoder(payload);
+        let mut chunk = [0; CHUNK_LEN];
+        loop {
+            match fill_chunk(&mut chunk, &mut encoder) {
+                0 => return Ok(()),
+                n => self.writer.write_all(&chunk[..n]).map_err(FrameError::Io)?,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<W: embedded_io_async::Write, C: Codec> FrameWriter<W, C> {
+    /// Encodes `payload` and its CRC, and writes the resulting frame asynchronously.
+    ///
+    /// If the future is dropped, the frame may be partially written.
+    pub async fn write_frame_async(&mut self, payload: &[u8]) -> Result<(), FrameError<W::Error>> {
+        let mut encoder = Self::encoder(payload);
+        let mut chunk = [0; CHUNK_LEN];
+        loop {
+            match fill_chunk(&mut chunk, &mut encoder) {
+                0 => return Ok(()),
+                n => self
+                    .writer
+                    .write_all(&chunk[..n])
+                    .await
+                    .map_err(FrameError::Io)?,
+            }
+        }
+    }
+}
+
+/// Reads frames from an [`embedded_io`] reader
+///
+/// Frames are decoded directly into the caller buffer, which must hold the payload and its CRC.
+/// If reading is interrupted (e.g., by an I/O error), the next call continues decoding
+/// the same frame, so the same buffer must be used.
+pub struct FrameReader<R, C: Codec> {
+    reader: R,
+    decoder: C::Decoder,
+    raw: [u8; RAW_LEN],
+    start: usize,
+    end: usize,
+}
+
+/// Reads COBS frames from an [`embedded_io`] reader
+pub type CobsReader<R> = FrameReader<R, Cobs>;
+
+/// Reads SLIP frames from an [`embedded_io`] reader
+pub type SlipReader<R> = FrameReader<R, Slip>;
+
+impl<R, C: Codec> FrameReader<R, C> {
+    /// Creates a new frame reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoder: C::Decoder::default(),
+            raw: [0; RAW_LEN],
+            start: 0,
+            end: 0,
+        }
+    }
+
+    /// Releases the underlying reader
+    ///
+    /// Bytes already read from the underlying reader but not decoded yet are lost.
+    pub fn free(self) -> R {
+        self.reader
+    }
+
+    /// Decodes the bytes read so far, returning the payload length once a frame is complete.
+    fn decode<E>(&mut self, buf: &mut [u8]) -> Option<Result<usize, FrameError<E>>> {
+        while self.start < self.end {
+            let byte = self.raw[self.start];
+            self.start += 1;
+            if let Some(res) = self.decoder.feed(byte, buf) {
+                return Some(
+                    res.map_err(FrameError::from)
+                        .and_then(|len| check_crc(buf, len)),
+                );
+            }
+        }
+        None
+    }
+}
+
+/// Checks the CRC at the end of a decoded frame, returning the payload length.
+fn check_crc<E>(buf: &[u8], len: usize) -> Result<usize, FrameError<E>> {
+    let payload_len = len.checked_sub(2).ok_or(FrameError::TooShort)?;
+    let crc = u16::from_be_bytes([buf[payload_len], buf[payload_len + 1]]);
+    match crc16(&buf[..payload_len]) == crc {
+        true => Ok(payload_len),
+        false => Err(FrameError::Crc),
+    }
+}
+
+impl<R: embedded_io::Read, C: Codec> FrameReader<R, C> {
+    /// Reads the next frame into `buf`, returning the length of its payload.
+    ///
+    /// # Errors
+    ///
+    /// Oversized, corrupted, and CRC mismatching frames are discarded and reported.
+    /// The next call reads the following frame.
+    pub fn read_frame(&mut self, buf: &mut [u8]) -> Result<usize, FrameError<R::Error>> {
+        loop {
+            if let Some(res) = self.decode(buf) {
+                return res;
+            }
+            self.end = match self.reader.read(&mut self.raw).map_err(FrameError::Io)? {
+                0 => return Err(FrameError::Eof),
+                n => n,
+            };
+            self.start = 0;
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: embedded_io_async::Read, C: Codec> FrameReader<R, C> {
+    /// Reads the next frame into `buf` asynchronously, returning the length of its payload.
+    ///
+    /// If the future is dropped, the next call continues decoding the same frame,
+    /// so the same buffer must be used.
+    ///
+    /// # Errors
+    ///
+    /// Oversized, corrupted, and CRC mismatching frames are discarded and reported.
+    /// The next call reads the following frame.
+    pub async fn read_frame_async(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<usize, FrameError<R::Error>> {
+        loop {
+            if let Some(res) = self.decode(buf) {
+                return res;
+            }
+            self.end = match self
+                .reader
+                .read(&mut self.raw)
+                .await
+                .map_err(FrameError::Io)?
+            {
+                0 => return Err(FrameError::Eof),
+                n => n,
+            };
+            self.start = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::{CobsReader, CobsWriter, SlipReader};
+    use embedded_io::{ErrorKind, ErrorType, Read, SliceWriteError};
+
+    /// Writes `payloads` as frames into `out`, returning the encoded length.
+    fn write_frames<C: Codec>(payloads: &[&[u8]], out: &mut [u8]) -> usize {
+        let total = out.len();
+        let mut writer = FrameWriter::<_, C>::new(out);
+        for payload in payloads {
+            writer.write_frame(payload).unwrap();
+        }
+        total - writer.free().len()
+    }
+
+    fn round_trip<C: Codec>() {
+        let mut long = [0; 300];
+        for (i, byte) in long.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        let payloads: [&[u8]; 4] = [b"", &[0x00, 0xc0, 0xdb, 0xdc, 0xdd], b"hello", &long];
+        let mut encoded = [0; 512];
+        let len = write_frames::<C>(&payloads, &mut encoded);
+
+        let mut reader = FrameReader::<_, C>::new(&encoded[..len]);
+        let mut buf = [0; 302];
+        for payload in payloads {
+            assert_eq!(reader.read_frame(&mut buf), Ok(payload.len()));
+            assert_eq!(&buf[..payload.len()], payload);
+        }
+        assert_eq!(reader.read_frame(&mut buf), Err(FrameError::Eof));
+    }
+
+    #[test]
+    fn cobs_round_trip() {
+        round_trip::<Cobs>();
+    }
+
+    #[test]
+    fn slip_round_trip() {
+        round_trip::<Slip>();
+    }
+
+    #[test]
+    fn crc_is_appended() {
+        let mut encoded = [0; 16];
+        let len = write_frames::<Slip>(&[b"123456789"], &mut encoded);
+        assert_eq!(&encoded[..len], b"\xc0123456789\x29\xb1\xc0");
+    }
+
+    #[test]
+    fn oversized_frame_is_skipped() {
+        let mut encoded = [0; 64];
+        let len = write_frames::<Cobs>(&[b"too long for the buffer", b"ok"], &mut encoded);
+
+        let mut reader = CobsReader::new(&encoded[..len]);
+        let mut buf = [0; 8];
+        assert_eq!(reader.read_frame(&mut buf), Err(FrameError::Oversized));
+        assert_eq!(reader.read_frame(&mut buf), Ok(2));
+        assert_eq!(&buf[..2], b"ok");
+    }
+
+    #[test]
+    fn crc_mismatch_is_detected() {
+        let mut encoded = [0; 64];
+        let len = write_frames::<Slip>(&[b"hello", b"world"], &mut encoded);
+        // Corrupt a payload byte of the first frame
+        encoded[2] ^= 0x01;
+
+        let mut reader = SlipReader::new(&encoded[..len]);
+        let mut buf = [0; 8];
+        assert_eq!(reader.read_frame(&mut buf), Err(FrameError::Crc));
+        assert_eq!(reader.read_frame(&mut buf), Ok(5));
+        assert_eq!(&buf[..5], b"world");
+    }
+
+    #[test]
+    fn truncated_and_corrupted_frames() {
+        let mut buf = [0; 8];
+        // A single byte cannot hold the CRC
+        let mut reader = CobsReader::new(&[0x02, 0x11, 0x00][..]);
+        assert_eq!(reader.read_frame(&mut buf), Err(FrameError::TooShort));
+        // The frame ends within a COBS group
+        let mut reader = CobsReader::new(&[0x05, 0x11, 0x00][..]);
+        assert_eq!(reader.read_frame(&mut buf), Err(FrameError::Corrupted));
+        // Invalid SLIP escape sequence
+        let mut reader = SlipReader::new(&[0xc0, 0xdb, 0x11, 0x22, 0x33, 0xc0][..]);
+        assert_eq!(reader.read_frame(&mut buf), Err(FrameError::Corrupted));
+        // The stream ends in the middle of a frame
+        let mut reader = SlipReader::new(&[0xc0, 0x11, 0x22, 0x33][..]);
+        assert_eq!(reader.read_frame(&mut buf), Err(FrameError::Eof));
+    }
+
+    #[test]
+    fn writer_error() {
+        let mut encoded = [0; 4];
+        let mut writer = CobsWriter::new(&mut encoded[..]);
+        assert_eq!(
+            writer.write_frame(b"hello"),
+            Err(FrameError::Io(SliceWriteError::Full))
+        );
+    }
+
+    /// Reader that returns one byte per call, and an error before every `fail_every` bytes
+    struct FlakyReader<'a> {
+        data: &'a [u8],
+        fail_every: usize,
+        count: usize,
+    }
+
+    impl ErrorType for FlakyReader<'_> {
+        type Error = ErrorKind;
+    }
+
+    impl Read for FlakyReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.count += 1;
+            if self.count % self.fail_every == 0 {
+                return Err(ErrorKind::Interrupted);
+            }
+            let Some((first, rest)) = self.data.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.data = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn reading_resumes_after_io_error() {
+        let mut encoded = [0; 32];
+        let len = write_frames::<Cobs>(&[b"abc\0def"], &mut encoded);
+
+        let mut reader = CobsReader::new(FlakyReader {
+            data: &encoded[..len],
+            fail_every: 4,
+            count: 0,
+        });
+        let mut buf = [0; 16];
+        let len = loop {
+            match reader.read_frame(&mut buf) {
+                Ok(len) => break len,
+                Err(e) => assert_eq!(e, FrameError::Io(ErrorKind::Interrupted)),
+            }
+        };
+        assert_eq!(&buf[..len], b"abc\0def");
+    }
+}
@@ -0,0 +1,236 @@
+use super::{Codec, DecodeError, Decoder};
+
+/// Frame delimiter
+const END: u8 = 0xc0;
+/// Escape byte
+const ESC: u8 = 0xdb;
+/// Escaped frame delimiter
+const ESC_END: u8 = 0xdc;
+/// Escaped escape byte
+const ESC_ESC: u8 = 0xdd;
+
+/// Serial Line Internet Protocol codec (RFC 1055)
+///
+/// Frames are delimited by `END` bytes. Encoded frames start with an `END` byte too,
+/// to flush any line noise received by the peer.
+pub struct Slip;
+
+impl Codec for Slip {
+    type Encoder<I: Iterator<Item = u8> + Clone> = SlipEncoder<I>;
+    type Decoder = SlipDecoder;
+
+    fn encode<I: Iterator<Item = u8> + Clone>(data: I) -> Self::Encoder<I> {
+        SlipEncoder::new(data)
+    }
+}
+
+/// Iterator over the SLIP encoded bytes of a frame, including its delimiters
+#[derive(Clone)]
+pub struct SlipEncoder<I> {
+    data: I,
+    started: bool,
+    done: bool,
+    escaped: Option<u8>,
+}
+
+impl<I: Iterator<Item = u8>> SlipEncoder<I> {
+    /// Creates a new encoder for the given data.
+    pub fn new(data: I) -> Self {
+        Self {
+            data,
+            started: false,
+            done: false,
+            escaped: None,
+        }
+    }
+}
+
+impl<I: Iterator<Item = u8>> Iterator for SlipEncoder<I> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if !self.started {
+            self.started = true;
+            return Some(END);
+        }
+        if let Some(byte) = self.escaped.take() {
+            return Some(byte);
+        }
+        if self.done {
+            return None;
+        }
+        match self.data.next() {
+            Some(END) => {
+                self.escaped = Some(ESC_END);
+                Some(ESC)
+            }
+            Some(ESC) => {
+                self.escaped = Some(ESC_ESC);
+                Some(ESC)
+            }
+            Some(byte) => Some(byte),
+            None => {
+                self.done = true;
+                Some(END)
+            }
+        }
+    }
+}
+
+/// Streaming SLIP decoder
+#[derive(Default)]
+pub struct SlipDecoder {
+    escaped: bool,
+    len: usize,
+    in_frame: bool,
+    error: Option<DecodeError>,
+}
+
+impl Decoder for SlipDecoder {
+    fn feed(&mut self, byte: u8, buf: &mut [u8]) -> Option<Result<usize, DecodeError>> {
+        if byte == END {
+            let res = match (self.in_frame, self.error, self.escaped) {
+                (false, _, _) => None,
+                (true, Some(e), _) => Some(Err(e)),
+                (true, None, false) => Some(Ok(self.len)),
+                // The frame ends with an escape byte
+                (true, None, true) => Some(Err(DecodeError::Corrupted)),
+            };
+            *self = Self::default();
+            return res;
+        }
+
+        self.in_frame = true;
+        if self.error.is_some() {
+            // Skip the rest of the frame
+            return None;
+        }
+        let byte = match (self.escaped, byte) {
+            (false, ESC) => {
+                self.escaped = true;
+                return None;
+            }
+            (false, byte) => byte,
+            (true, ESC_END) => END,
+            (true, ESC_ESC) => ESC,
+            (true, _) => {
+                self.error = Some(DecodeError::Corrupted);
+                return None;
+            }
+        };
+        self.escaped = false;
+        match buf.get_mut(self.len) {
+            Some(b) => {
+                *b = byte;
+                self.len += 1;
+            }
+            None => self.error = Some(DecodeError::Oversized),
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes `data` into `out`, returning the encoded length.
+    fn encode(data: &[u8], out: &mut [u8]) -> usize {
+        let mut len = 0;
+        for (o, byte) in out.iter_mut().zip(Slip::encode(data.iter().copied())) {
+            *o = byte;
+            len += 1;
+        }
+        assert!(len < out.len(), "output buffer too small");
+        len
+    }
+
+    /// Feeds `encoded` to a new decoder, returning the result of the first complete frame.
+    fn decode(encoded: &[u8], buf: &mut [u8]) -> Option<Result<usize, DecodeError>> {
+        let mut decoder = SlipDecoder::default();
+        encoded.iter().find_map(|byte| decoder.feed(*byte, buf))
+    }
+
+    fn round_trip(data: &[u8]) {
+        let mut encoded = [0; 64];
+        let len = encode(data, &mut encoded);
+        assert_eq!(encoded[0], END);
+        assert_eq!(encoded[len - 1], END);
+        assert!(!encoded[1..len - 1].contains(&END));
+
+        let mut buf = [0; 32];
+        assert_eq!(decode(&encoded[..len], &mut buf), Some(Ok(data.len())));
+        assert_eq!(&buf[..data.len()], data);
+    }
+
+    #[test]
+    fn encode_known_vectors() {
+        let mut encoded = [0; 16];
+        let len = encode(&[0x01, END, ESC, 0x02], &mut encoded);
+        assert_eq!(
+            &encoded[..len],
+            &[END, 0x01, ESC, ESC_END, ESC, ESC_ESC, 0x02, END]
+        );
+        let len = encode(&[ESC_END, ESC_ESC], &mut encoded);
+        assert_eq!(&encoded[..len], &[END, ESC_END, ESC_ESC, END]);
+    }
+
+    #[test]
+    fn round_trip_with_special_bytes() {
+        round_trip(&[0x00]);
+        round_trip(&[END]);
+        round_trip(&[ESC]);
+        round_trip(&[END, END, ESC, ESC]);
+        round_trip(&[ESC, ESC_END, END, ESC_ESC]);
+        round_trip(&[0x00, 0x11, END, 0x22, ESC, 0x00]);
+    }
+
+    #[test]
+    fn empty_frames_are_skipped() {
+        let mut encoded = [0; 4];
+        let len = encode(&[], &mut encoded);
+        assert_eq!(&encoded[..len], &[END, END]);
+
+        let mut buf = [0; 4];
+        assert_eq!(decode(&encoded[..len], &mut buf), None);
+        assert_eq!(decode(&[END, END, END, 0x11, END], &mut buf), Some(Ok(1)));
+        assert_eq!(buf[0], 0x11);
+    }
+
+    #[test]
+    fn oversized_frame() {
+        let mut encoded = [0; 16];
+        let len = encode(&[1, 2, 3, 4, 5], &mut encoded);
+        let mut buf = [0; 4];
+        assert_eq!(
+            decode(&encoded[..len], &mut buf),
+            Some(Err(DecodeError::Oversized))
+        );
+
+        // The decoder resynchronizes on the next delimiter
+        let mut decoder = SlipDecoder::default();
+        let results = encoded[..len]
+            .iter()
+            .chain(&[0x11, ESC, ESC_END, END])
+            .filter_map(|byte| decoder.feed(*byte, &mut buf));
+        assert!(results.eq([Err(DecodeError::Oversized), Ok(2)]));
+        assert_eq!(&buf[..2], &[0x11, END]);
+    }
+
+    #[test]
+    fn corrupted_frame() {
+        let mut buf = [0; 16];
+        // Invalid escape sequence
+        assert_eq!(
+            decode(&[END, 0x11, ESC, 0x22, END], &mut buf),
+            Some(Err(DecodeError::Corrupted))
+        );
+        // The frame ends with an escape byte
+        assert_eq!(
+            decode(&[END, 0x11, ESC, END], &mut buf),
+            Some(Err(DecodeError::Corrupted))
+        );
+        // Incomplete frames are not reported
+        assert_eq!(decode(&[END, 0x11, 0x22], &mut buf), None);
+    }
+}
@@ -91,7 +91,11 @@ pub struct NoInvert;
 /// Invert output mode (type state)
 pub struct Invert;
 
-trait PinIndex {
+/// Index of a pin within its GPIO block
+///
+/// This trait is sealed and cannot be implemented outside of this crate.
+pub trait PinIndex: private::Sealed {
+    /// Pin index
     const INDEX: usize;
 }
 
@@ -104,6 +108,62 @@ fn atomic_set_bit(r: &AtomicU32, index: usize, bit: bool) {
     };
 }
 
+/// Sets the output value of a pin of the GPIO0 block by index.
+///
+/// Used by peripherals that drive pins from interrupt handlers.
+pub(crate) fn set_pin_output(index: usize, bit: bool) {
+    let p = unsafe { e310x::Gpio0::steal() };
+    let r: &AtomicU32 = unsafe { core::mem::transmute(p.output_val()) };
+    atomic_set_bit(r, index, bit);
+}
+
+/// Drives a pin of the GPIO0 block by index to a physical `level`.
+///
+/// Unlike [`set_pin_output`], the output XOR of the pin is taken into account,
+/// so inverted outputs are driven to the same electrical level as regular ones.
+pub(crate) fn set_pin_level(index: usize, level: bool) {
+    let p = unsafe { e310x::Gpio0::steal() };
+    let xor = (p.out_xor().read().bits() >> (index & 31) & 1) != 0;
+    set_pin_output(index, level ^ xor);
+}
+
+/// Returns the physical level driven on a pin of the GPIO0 block by index.
+///
+/// The output XOR of the pin is taken into account.
+pub(crate) fn pin_level(index: usize) -> bool {
+    let p = unsafe { e310x::Gpio0::steal() };
+    let bits = p.output_val().read().bits() ^ p.out_xor().read().bits();
+    (bits >> (index & 31) & 1) != 0
+}
+
+/// Returns the input value of a pin of the GPIO0 block by index.
+pub(crate) fn pin_input(index: usize) -> bool {
+    let p = unsafe { e310x::Gpio0::steal() };
+    (p.input_val().read().bits() >> (index & 31) & 1) != 0
+}
+
+/// Enables or disables the input of a pin of the GPIO0 block by index.
+pub(crate) fn set_pin_input_en(index: usize, bit: bool) {
+    let p = unsafe { e310x::Gpio0::steal() };
+    let r: &AtomicU32 = unsafe { core::mem::transmute(p.input_en()) };
+    atomic_set_bit(r, index, bit);
+}
+
+/// Enables or disables the output of a pin of the GPIO0 block by index.
+#[cfg(feature = "g002")]
+pub(crate) fn set_pin_output_en(index: usize, bit: bool) {
+    let p = unsafe { e310x::Gpio0::steal() };
+    let r: &AtomicU32 = unsafe { core::mem::transmute(p.output_en()) };
+    atomic_set_bit(r, index, bit);
+}
+
+/// Enables or disables the I/O function of a pin of the GPIO0 block by index.
+pub(crate) fn set_pin_iof_en(index: usize, bit: bool) {
+    let p = unsafe { e310x::Gpio0::steal() };
+    let r: &AtomicU32 = unsafe { core::mem::transmute(p.iof_en()) };
+    atomic_set_bit(r, index, bit);
+}
+
 trait PeripheralAccess {
     fn peripheral() -> e310x::Gpio0;
 
@@ -220,6 +280,8 @@ macro_rules! gpio {
                     _mode: PhantomData<MODE>,
                 }
 
+                impl<MODE> super::private::Sealed for $PXi<MODE> {}
+
                 impl<MODE> PinIndex for $PXi<MODE> {
                     const INDEX: usize = $i;
                 }
@@ -652,3 +714,7 @@ gpio!(Gpio0, gpio0, [
     Pin30: (pin30, 30, GPIO30, Unknown),
     Pin31: (pin31, 31, GPIO31, Unknown),
 ]);
+
+mod private {
+    pub trait Sealed {}
+}
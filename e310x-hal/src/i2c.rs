@@ -9,15 +9,92 @@
 //! - SDA: Pin 12 IOF0
 //! - SCL: Pin 13 IOF0
 //! - Interrupt::I2C0
-
-use crate::{clock::Clocks, time::Bps};
-use core::ops::Deref;
+//!
+//! # Bus frequency
+//!
+//! Any bus frequency can be requested with a [`Speed`] preset or a [`Hertz`] value. The
+//! prescaler is computed from the `tlclk` frequency so the achieved frequency, returned by
+//! [`I2c::frequency`], is the closest one that does not exceed the requested frequency.
+//! Use [`I2c::with_frequency`] to get an error for unreachable frequencies, and
+//! [`I2c::set_frequency`] to change it at runtime (e.g., after changing the clocks).
+//!
+//! # Shared bus
+//!
+//! [`I2c::shared`] wraps the bus in a [`SharedI2c`] that hands out [`I2cDevice`] handles, one
+//! per driver. Each device may use its own bus frequency, which is set when it acquires the bus.
+//! See also [`asynch::i2c::SharedI2c`](crate::asynch::i2c::SharedI2c) for async drivers.
+//!
+//!```ignore
+//! let i2c = I2c::new(p.I2C0, sda, scl, Speed::Fast, clocks);
+//! let bus = i2c.shared();
+//!
+//! let mut sensor = bus.new_device();
+//! let mut eeprom = bus.new_device_with_frequency(100.khz().into(), clocks)?;
+//!```
+//!
+//! # Bus scan
+//!
+//! [`I2c::scan`] probes every non-reserved 7-bit address with a zero-length write and returns
+//! a [`ScanResult`] with the addresses that acknowledged. A single device can be probed with
+//! [`I2c::probe`].
+//!
+//!```ignore
+//! for address in i2c.scan(Duration::from_millis(5))?.iter() {
+//!     sprintln!("found device at {:#04x}", address);
+//! }
+//!```
+//!
+//! # 10-bit addressing
+//!
+//! [`I2c`] implements [`embedded_hal::i2c::I2c`] for both 7-bit ([`u8`]) and 10-bit ([`u16`])
+//! addresses. 10-bit devices are addressed with the `11110xx` header followed by the low byte
+//! of the address. Reads send the header again in read mode after a repeated START.
+//!
+//!```ignore
+//! i2c.write_read(0x2A5u16, &[REGISTER], &mut buffer)?;
+//!```
+//!
+//! # Timeouts
+//!
+//! By default, transactions wait forever for the bus. With [`I2c::set_timeout`], every blocking
+//! and async transaction fails with [`Error::Timeout`] if it does not complete in time (e.g.,
+//! when a device holds SDA low after a brown-out). The controller cannot abort a transfer in
+//! progress, so after an [`Error::Timeout`] the bus must be released with [`I2c::recover_bus`]
+//! before the next transaction.
+
+use crate::{
+    clock::Clocks,
+    gpio::{pin_input, set_pin_input_en, set_pin_iof_en, set_pin_output, set_pin_output_en},
+    time::{Bps, Hertz},
+};
+use core::{ops::Deref, time::Duration};
 use e310x::{i2c0, I2c0};
 use e310x::{
     interrupt::{ExternalInterrupt, Priority},
-    Plic,
+    Clint, Plic,
+};
+use embedded_hal::i2c::{
+    self, ErrorKind, ErrorType, NoAcknowledgeSource, Operation, TenBitAddress,
 };
-use embedded_hal::i2c::{self, ErrorKind, ErrorType, NoAcknowledgeSource, Operation};
+
+mod scan; // contains the bus scanner and device probing
+mod shared; // contains the shared I2C bus and device abstractions
+mod ten_bit; // contains the 10-bit addressing implementation
+
+pub use scan::*;
+pub use shared::*;
+
+/// Index of the SDA pin of I2C0
+const SDA_INDEX: usize = 12;
+
+/// Index of the SCL pin of I2C0
+const SCL_INDEX: usize = 13;
+
+/// Maximum value of the prescaler register
+const PRER_MAX: u32 = 0xffff;
+
+/// Maximum time a device may stretch SCL during [`I2c::recover_bus`] without a timeout
+const RECOVERY_STRETCH_TIMEOUT: Duration = Duration::from_millis(25);
 
 /// SDA pin
 pub trait SdaPin<I2C>: private::Sealed {}
@@ -39,6 +116,7 @@ mod i2c_impl {
 }
 
 /// Transmission speed
+#[derive(Clone, Copy)]
 pub enum Speed {
     /// 100Kbps
     Normal,
@@ -46,63 +124,246 @@ pub enum Speed {
     /// 400Kbps
     Fast,
 
+    /// 1Mbps
+    FastPlus,
+
     /// Custom speed
     Custom(Bps),
 }
 
+impl From<Speed> for Hertz {
+    fn from(speed: Speed) -> Self {
+        match speed {
+            Speed::Normal => Hertz(100_000),
+            Speed::Fast => Hertz(400_000),
+            Speed::FastPlus => Hertz(1_000_000),
+            Speed::Custom(bps) => Hertz(bps.0),
+        }
+    }
+}
+
+/// Errors when computing the I2C bus frequency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrequencyError {
+    /// The requested frequency is higher than a fifth of the TileLink bus clock
+    TooHigh,
+    /// The requested frequency needs a prescaler that does not fit in the `prer` registers
+    TooLow,
+}
+
+/// Computes the prescaler for the requested frequency and the achieved frequency.
+///
+/// # Errors
+///
+/// See [`I2c::with_frequency`].
+///
+/// The achieved frequency is the closest one that does not exceed the requested frequency.
+pub(crate) fn prescaler(frequency: Hertz, clocks: &Clocks) -> Result<(u32, Hertz), FrequencyError> {
+    let tlclk = clocks.tlclk().0;
+    if frequency.0 == 0 {
+        return Err(FrequencyError::TooLow);
+    }
+    if frequency.0 > tlclk / 5 {
+        return Err(FrequencyError::TooHigh);
+    }
+    // scl = tlclk / (5 * (prescaler + 1))
+    let prescaler = tlclk.div_ceil(5 * frequency.0) - 1;
+    if prescaler > PRER_MAX {
+        return Err(FrequencyError::TooLow);
+    }
+    Ok((prescaler, Hertz(tlclk / (5 * (prescaler + 1)))))
+}
+
+/// I2C error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Arbitration was lost
+    ArbitrationLoss,
+    /// The device did not acknowledge its address or a data byte
+    NoAcknowledge(NoAcknowledgeSource),
+    /// The transaction did not complete within the configured timeout
+    Timeout,
+    /// SDA or SCL are still held low after [`I2c::recover_bus`]
+    BusStuck,
+    /// The shared bus is in use by another device (e.g., from an interrupt handler)
+    Busy,
+}
+
+impl i2c::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ArbitrationLoss => ErrorKind::ArbitrationLoss,
+            Self::NoAcknowledge(source) => ErrorKind::NoAcknowledge(*source),
+            Self::Timeout => ErrorKind::Other,
+            Self::BusStuck => ErrorKind::Bus,
+            Self::Busy => ErrorKind::Other,
+        }
+    }
+}
+
+/// Returns the current value of the `mtime` counter.
+fn mtime() -> u64 {
+    unsafe { Clint::steal() }.mtimer().mtime().read()
+}
+
+/// Converts a duration to `mtime` ticks, rounding up.
+pub(crate) fn duration_ticks(duration: Duration) -> u64 {
+    let mtime_freq = unsafe { Clint::steal() }.mtimer().mtime_freq();
+    let ticks = (duration.as_nanos() * mtime_freq as u128).div_ceil(1_000_000_000);
+    (ticks as u64).max(1)
+}
+
+/// Deadline of a blocking operation, in `mtime` ticks
+#[derive(Clone, Copy)]
+struct Deadline(Option<u64>);
+
+impl Deadline {
+    fn new(timeout: Option<Duration>) -> Self {
+        Self(timeout.map(|timeout| mtime() + duration_ticks(timeout)))
+    }
+
+    fn expired(&self) -> bool {
+        self.0.is_some_and(|deadline| mtime() >= deadline)
+    }
+}
+
 /// I2C abstraction
 pub struct I2c<I2C, PINS> {
     pub(crate) i2c: I2C,
     pins: PINS,
+    pub(crate) timeout: Option<Duration>,
+    prescaler: u32,
+    frequency: Hertz,
 }
 
 impl<I2C: I2cX, SDA, SCL> I2c<I2C, (SDA, SCL)> {
     /// Configures an I2C peripheral
-    pub fn new(i2c: I2C, sda: SDA, scl: SCL, speed: Speed, clocks: Clocks) -> Self
+    ///
+    /// `speed` can be a [`Speed`] preset or any [`Hertz`] value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the frequency cannot be reached. Use [`I2c::with_frequency`] to get an error instead.
+    pub fn new(i2c: I2C, sda: SDA, scl: SCL, speed: impl Into<Hertz>, clocks: Clocks) -> Self
     where
         SDA: SdaPin<I2C>,
         SCL: SclPin<I2C>,
     {
-        // Calculate prescaler value
-        let desired_speed = match speed {
-            Speed::Normal => 100_000,
-            Speed::Fast => 400_000,
-            Speed::Custom(bps) => bps.0,
+        Self::with_frequency(i2c, sda, scl, speed.into(), clocks)
+            .expect("I2C frequency out of range")
+    }
+
+    /// Configures an I2C peripheral with the closest bus frequency that does not exceed `frequency`
+    ///
+    /// # Errors
+    ///
+    /// * [`FrequencyError::TooHigh`] - the frequency is higher than a fifth of the `tlclk` frequency
+    /// * [`FrequencyError::TooLow`] - the prescaler does not fit in the `prer` registers
+    pub fn with_frequency(
+        i2c: I2C,
+        sda: SDA,
+        scl: SCL,
+        frequency: Hertz,
+        clocks: Clocks,
+    ) -> Result<Self, FrequencyError>
+    where
+        SDA: SdaPin<I2C>,
+        SCL: SclPin<I2C>,
+    {
+        let (prescaler, frequency) = prescaler(frequency, &clocks)?;
+        let mut i2c = Self {
+            i2c,
+            pins: (sda, scl),
+            timeout: None,
+            prescaler,
+            frequency,
         };
-        let clock = clocks.tlclk().0;
-        assert!(desired_speed * 5 <= clock);
-        let prescaler = clock / (5 * desired_speed) - 1;
-        assert!(prescaler < (1 << 16));
+        i2c.configure(prescaler, false);
+        Ok(i2c)
+    }
+}
 
+impl<I2C, PINS> I2c<I2C, PINS> {
+    /// Releases the I2C peripheral and associated pins
+    pub fn free(self) -> (I2C, PINS) {
+        (self.i2c, self.pins)
+    }
+
+    /// Returns the achieved bus frequency.
+    pub fn frequency(&self) -> Hertz {
+        self.frequency
+    }
+
+    /// Returns the timeout of the transactions, if any.
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// Sets the timeout of the transactions, or disables it with `None`.
+    ///
+    /// A transaction that does not complete in time fails with [`Error::Timeout`],
+    /// and the bus must then be released with [`I2c::recover_bus`].
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
+    /// Writes the prescaler with the peripheral disabled, and enables it again.
+    fn configure(&mut self, prescaler: u32, ien: bool) {
         // Turn off i2c
-        i2c.ctr().write(|w| w.en().clear_bit().ien().clear_bit());
+        self.i2c
+            .ctr()
+            .write(|w| w.en().clear_bit().ien().clear_bit());
 
         // Set prescaler
         let prescaler_lo = (prescaler & 0xff) as u8;
         let prescaler_hi = ((prescaler >> 8) & 0xff) as u8;
-        i2c.prer_lo()
+        self.i2c
+            .prer_lo()
             .write(|w| unsafe { w.value().bits(prescaler_lo) });
-        i2c.prer_hi()
+        self.i2c
+            .prer_hi()
             .write(|w| unsafe { w.value().bits(prescaler_hi) });
 
         // Turn on i2c
-        i2c.ctr().write(|w| w.en().set_bit());
+        self.i2c.ctr().write(|w| w.en().set_bit().ien().bit(ien));
+    }
 
-        Self {
-            i2c,
-            pins: (sda, scl),
+    /// Changes the bus frequency, e.g., after changing the clocks.
+    ///
+    /// Returns the achieved frequency, which is the closest one that does not exceed `frequency`.
+    /// It must not be called while an async transaction is in progress.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frequency cannot be reached. The frequency is not changed in that case.
+    /// See [`I2c::with_frequency`] for more details.
+    pub fn set_frequency(
+        &mut self,
+        frequency: Hertz,
+        clocks: Clocks,
+    ) -> Result<Hertz, FrequencyError> {
+        let (prescaler, frequency) = prescaler(frequency, &clocks)?;
+        self.set_prescaler(prescaler, frequency);
+        Ok(frequency)
+    }
+
+    /// Writes a precomputed prescaler, if it differs from the current one.
+    pub(crate) fn set_prescaler(&mut self, prescaler: u32, frequency: Hertz) {
+        if prescaler != self.prescaler {
+            let ien = self.is_interrupt_enabled();
+            self.configure(prescaler, ien);
+            self.prescaler = prescaler;
         }
+        self.frequency = frequency;
     }
-}
 
-impl<I2C, PINS> I2c<I2C, PINS> {
-    /// Releases the I2C peripheral and associated pins
-    pub fn free(self) -> (I2C, PINS) {
-        (self.i2c, self.pins)
+    /// Returns the current prescaler and the achieved bus frequency.
+    pub(crate) fn speed(&self) -> (u32, Hertz) {
+        (self.prescaler, self.frequency)
     }
-}
 
-impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
     /// Enables the external interrupt source for the I2C.
     ///
     /// # Note
@@ -262,8 +523,17 @@ impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
     }
 
     /// Blocking version of [`Self::is_idle`].
-    fn wait_idle(&self) {
-        while !self.is_idle() {}
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the bus is not idle before the deadline.
+    fn wait_idle(&self, deadline: Deadline) -> Result<(), Error> {
+        while !self.is_idle() {
+            if deadline.expired() {
+                return Err(Error::Timeout);
+            }
+        }
+        Ok(())
     }
 
     /// Acknowledge an interrupt.
@@ -271,13 +541,13 @@ impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
     /// # Errors
     ///
     /// In case of arbitration loss, a stop condition is sent
-    /// and an [`ErrorKind::ArbitrationLoss`] is returned.
-    pub(crate) fn ack_interrupt(&self) -> nb::Result<(), ErrorKind> {
+    /// and an [`Error::ArbitrationLoss`] is returned.
+    pub(crate) fn ack_interrupt(&self) -> nb::Result<(), Error> {
         let sr = self.read_sr();
         if sr.tip().bit_is_clear() {
             if sr.al().bit_is_set() {
                 self.set_stop();
-                Err(nb::Error::Other(ErrorKind::ArbitrationLoss))
+                Err(nb::Error::Other(Error::ArbitrationLoss))
             } else {
                 Ok(())
             }
@@ -291,9 +561,25 @@ impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
     /// # Errors
     ///
     /// In case of arbitration loss it waits until the bus is idle
-    /// before returning an [`ErrorKind::ArbitrationLoss`] error.
-    fn wait_for_read(&self) -> Result<(), ErrorKind> {
-        nb::block!(self.ack_interrupt())
+    /// before returning an [`Error::ArbitrationLoss`] error.
+    ///
+    /// If the operation does not complete before the deadline, an [`Error::Timeout`] is
+    /// returned and the transfer is left in progress (see [`I2c::recover_bus`]).
+    fn wait_for_read(&self, deadline: Deadline) -> Result<(), Error> {
+        self.wait_transfer(deadline)
+    }
+
+    /// Wait for the current transfer to complete.
+    fn wait_transfer(&self, deadline: Deadline) -> Result<(), Error> {
+        loop {
+            match self.ack_interrupt() {
+                Ok(()) => return Ok(()),
+                Err(nb::Error::Other(e)) => return Err(e),
+                // STOP requests are ignored while the transfer is in progress
+                Err(nb::Error::WouldBlock) if deadline.expired() => return Err(Error::Timeout),
+                Err(nb::Error::WouldBlock) => {}
+            }
+        }
     }
 
     /// Wait for a write operation to complete.
@@ -301,26 +587,100 @@ impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
     /// # Errors
     ///
     /// If the slave device does not acknowledge the write, a stop condition
-    /// is sent and an [`ErrorKind::NoAcknowledge`] is returned.
+    /// is sent and an [`Error::NoAcknowledge`] is returned.
     ///
     /// In case of arbitration loss it waits until the bus is idle
-    /// before returning an [`ErrorKind::ArbitrationLoss`] error.
-    fn wait_for_write(&self, source: NoAcknowledgeSource) -> Result<(), ErrorKind> {
-        nb::block!(self.ack_interrupt())?;
+    /// before returning an [`Error::ArbitrationLoss`] error.
+    ///
+    /// If the operation does not complete before the deadline, an [`Error::Timeout`] is
+    /// returned and the transfer is left in progress (see [`I2c::recover_bus`]).
+    fn wait_for_write(&self, source: NoAcknowledgeSource, deadline: Deadline) -> Result<(), Error> {
+        self.wait_transfer(deadline)?;
         if self.read_sr().rx_ack().bit_is_set() {
             self.set_stop();
-            Err(ErrorKind::NoAcknowledge(source))
+            Err(Error::NoAcknowledge(source))
         } else {
             Ok(())
         }
     }
+
+    /// Releases a bus held by a device (e.g., SDA stuck low after a reset in the middle of a read).
+    ///
+    /// The SDA (12) and SCL (13) pins are temporarily used as open-drain GPIOs: up to nine
+    /// SCL pulses are clocked out until the device releases SDA, and a STOP condition is sent.
+    /// Then, the pins are given back to the I2C peripheral. The bus is clocked at about 10 kHz.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BusStuck`] if SDA or SCL are still held low afterwards.
+    pub fn recover_bus(&mut self) -> Result<(), Error> {
+        let ien = self.i2c.ctr().read().ien().bit();
+        self.i2c
+            .ctr()
+            .write(|w| w.en().clear_bit().ien().clear_bit());
+
+        // Released lines are pulled up externally, so pins only drive low
+        for index in [SDA_INDEX, SCL_INDEX] {
+            set_pin_output(index, false);
+            set_pin_output_en(index, false);
+            set_pin_input_en(index, true);
+            set_pin_iof_en(index, false);
+        }
+        let stretch_timeout = self.timeout.unwrap_or(RECOVERY_STRETCH_TIMEOUT);
+        let half_period = || {
+            // Wait for at least one full `mtime` tick
+            let start = mtime();
+            while mtime() < start + 2 {}
+        };
+        let release_scl = || {
+            set_pin_output_en(SCL_INDEX, false);
+            // The device may stretch the clock
+            let deadline = Deadline::new(Some(stretch_timeout));
+            while !pin_input(SCL_INDEX) && !deadline.expired() {}
+            half_period();
+        };
+
+        for _ in 0..9 {
+            if pin_input(SDA_INDEX) {
+                break;
+            }
+            set_pin_output_en(SCL_INDEX, true);
+            half_period();
+            release_scl();
+        }
+
+        // STOP condition: SDA rises while SCL is high
+        set_pin_output_en(SCL_INDEX, true);
+        half_period();
+        set_pin_output_en(SDA_INDEX, true);
+        half_period();
+        release_scl();
+        set_pin_output_en(SDA_INDEX, false);
+        half_period();
+        let released = pin_input(SDA_INDEX) && pin_input(SCL_INDEX);
+
+        for index in [SDA_INDEX, SCL_INDEX] {
+            set_pin_iof_en(index, true);
+        }
+        self.i2c.ctr().write(|w| w.en().set_bit().ien().bit(ien));
+
+        match released {
+            true => Ok(()),
+            false => Err(Error::BusStuck),
+        }
+    }
 }
 
 const FLAG_READ: u8 = 1;
 const FLAG_WRITE: u8 = 0;
 
+/// Returns the first byte of a 10-bit address (`11110` followed by the two MSBs of the address).
+pub(crate) fn ten_bit_header(address: TenBitAddress) -> u8 {
+    0b1111_0000 | ((address >> 7) as u8 & 0b0000_0110)
+}
+
 impl<I2C: I2cX, PINS> ErrorType for I2c<I2C, PINS> {
-    type Error = ErrorKind;
+    type Error = Error;
 }
 
 impl<I2C: I2cX, PINS> i2c::I2c for I2c<I2C, PINS> {
@@ -334,7 +694,8 @@ impl<I2C: I2cX, PINS> i2c::I2c for I2c<I2C, PINS> {
             return Ok(());
         }
 
-        self.wait_idle();
+        let deadline = Deadline::new(self.timeout);
+        self.wait_idle(deadline)?;
         self.reset();
 
         // we use this flag to detect when we need to send a (repeated) start
@@ -349,7 +710,7 @@ impl<I2C: I2cX, PINS> i2c::I2c for I2c<I2C, PINS> {
                     // Send write command
                     self.write_txr((address << 1) + FLAG_WRITE);
                     self.trigger_write(last_op_was_read, false);
-                    self.wait_for_write(NoAcknowledgeSource::Address)?;
+                    self.wait_for_write(NoAcknowledgeSource::Address, deadline)?;
                     last_op_was_read = false;
 
                     // Write bytes
@@ -357,29 +718,27 @@ impl<I2C: I2cX, PINS> i2c::I2c for I2c<I2C, PINS> {
                     for (j, byte) in bytes.iter().enumerate() {
                         self.write_txr(*byte);
                         self.trigger_write(false, (i == n_ops - 1) && (j == n_bytes - 1));
-                        self.wait_for_write(NoAcknowledgeSource::Data)?;
+                        self.wait_for_write(NoAcknowledgeSource::Data, deadline)?;
                     }
                 }
                 Operation::Read(buffer) => {
                     // Send read command
                     self.write_txr((address << 1) + FLAG_READ);
                     self.trigger_write(!last_op_was_read, false);
-                    self.wait_for_write(NoAcknowledgeSource::Address)?;
+                    self.wait_for_write(NoAcknowledgeSource::Address, deadline)?;
                     last_op_was_read = true;
 
                     // Read bytes
                     let n_bytes = buffer.len();
                     for (j, byte) in buffer.iter_mut().enumerate() {
                         self.trigger_read(j == n_bytes - 1, (i == n_ops - 1) && (j == n_bytes - 1));
-                        self.wait_for_read()?;
+                        self.wait_for_read(deadline)?;
                         *byte = self.read_rxr();
                     }
                 }
             }
         }
-        self.wait_idle();
-
-        Ok(())
+        self.wait_idle(deadline)
     }
 }
 
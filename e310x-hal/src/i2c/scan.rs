@@ -0,0 +1,107 @@
+use core::time::Duration;
+use embedded_hal::i2c::NoAcknowledgeSource;
+
+use super::{Deadline, Error, I2c, I2cX, FLAG_WRITE};
+
+/// First 7-bit address probed by [`I2c::scan`] (`0x00` to `0x07` are reserved)
+pub(crate) const SCAN_FIRST: u8 = 0x08;
+
+/// Last 7-bit address probed by [`I2c::scan`] (`0x78` to `0x7F` are reserved)
+pub(crate) const SCAN_LAST: u8 = 0x77;
+
+/// Set of 7-bit addresses that acknowledged a bus scan
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ScanResult(u128);
+
+impl ScanResult {
+    /// Returns the bitmap of the responding addresses (bit `n` is set if address `n` responded).
+    pub fn bits(&self) -> u128 {
+        self.0
+    }
+
+    /// Returns true if the device at `address` responded.
+    pub fn contains(&self, address: u8) -> bool {
+        address < 128 && self.0 & (1 << address) != 0
+    }
+
+    /// Returns the number of responding addresses.
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// Returns true if no device responded.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns an iterator over the responding addresses, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u8> {
+        let result = *self;
+        (0..128).filter(move |&address| result.contains(address))
+    }
+
+    pub(crate) fn insert(&mut self, address: u8) {
+        self.0 |= 1 << address;
+    }
+}
+
+impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
+    /// Probes the device at a 7-bit `address` with a zero-length write.
+    ///
+    /// Returns `true` if the device acknowledged its address, and `false` otherwise.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::ArbitrationLoss`] - another master took the bus
+    /// * [`Error::Timeout`] - the probe did not complete within the timeout set with [`I2c::set_timeout`]
+    pub fn probe(&mut self, address: u8) -> Result<bool, Error> {
+        self.probe_until(address, Deadline::new(self.timeout))
+    }
+
+    /// Probes every non-reserved 7-bit address (`0x08` to `0x77`) with a zero-length write.
+    ///
+    /// Each address is given `timeout` to complete its probe. Addresses that time out once
+    /// addressed are reported as absent, and the bus is released with [`I2c::recover_bus`],
+    /// so a misbehaving device cannot hang the scan.
+    ///
+    /// # Errors
+    ///
+    /// * [`Error::ArbitrationLoss`] - another master took the bus
+    /// * [`Error::Timeout`] - the bus is not idle before a probe (e.g., SDA is held low)
+    /// * [`Error::BusStuck`] - the bus cannot be released after a device timed out
+    pub fn scan(&mut self, timeout: Duration) -> Result<ScanResult, Error> {
+        let mut result = ScanResult::default();
+        for address in SCAN_FIRST..=SCAN_LAST {
+            let deadline = Deadline::new(Some(timeout));
+            self.wait_idle(deadline)?;
+            match self.probe_idle(address, deadline) {
+                Ok(true) => result.insert(address),
+                Ok(false) => {}
+                Err(Error::Timeout) => self.recover_bus()?,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Waits for the bus to be idle and probes an address.
+    fn probe_until(&mut self, address: u8, deadline: Deadline) -> Result<bool, Error> {
+        self.wait_idle(deadline)?;
+        self.probe_idle(address, deadline)
+    }
+
+    /// Sends the address with START and STOP conditions on an idle bus, and waits for the bus to be idle.
+    fn probe_idle(&mut self, address: u8, deadline: Deadline) -> Result<bool, Error> {
+        self.reset();
+
+        self.write_txr((address << 1) + FLAG_WRITE);
+        self.trigger_write(true, true);
+        let present = match self.wait_for_write(NoAcknowledgeSource::Address, deadline) {
+            Ok(()) => true,
+            Err(Error::NoAcknowledge(_)) => false,
+            Err(e) => return Err(e),
+        };
+        self.wait_idle(deadline)?;
+        Ok(present)
+    }
+}
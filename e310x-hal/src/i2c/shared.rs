@@ -0,0 +1,91 @@
+use core::cell::RefCell;
+use embedded_hal::i2c::{ErrorType, Operation};
+use riscv::interrupt;
+
+use super::{prescaler, Error, FrequencyError, I2c, I2cX};
+use crate::{clock::Clocks, time::Hertz};
+
+impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
+    /// Create a [`SharedI2c`] for use with multiple devices.
+    pub fn shared(self) -> SharedI2c<I2C, PINS> {
+        SharedI2c::new(self)
+    }
+}
+
+/// Newtype for RefCell<I2c> locked behind a critical section.
+/// Used to hold the [I2c] instance so it can be used for multiple [I2cDevice] instances.
+pub struct SharedI2c<I2C, PINS> {
+    bus: RefCell<I2c<I2C, PINS>>,
+    /// Prescaler and frequency of the devices without a specific frequency
+    speed: (u32, Hertz),
+}
+
+impl<I2C: I2cX, PINS> SharedI2c<I2C, PINS> {
+    pub(crate) fn new(bus: I2c<I2C, PINS>) -> Self {
+        let speed = bus.speed();
+        Self {
+            bus: RefCell::new(bus),
+            speed,
+        }
+    }
+
+    /// Create a new shared device on this I2C bus, using the frequency of the bus when it was shared.
+    pub fn new_device(&self) -> I2cDevice<'_, I2C, PINS> {
+        I2cDevice {
+            bus: self,
+            speed: self.speed,
+        }
+    }
+
+    /// Create a new shared device on this I2C bus with its own bus frequency.
+    ///
+    /// The frequency is set every time the device acquires the bus, if it differs from the current one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the frequency cannot be reached. See [`I2c::with_frequency`] for more details.
+    pub fn new_device_with_frequency(
+        &self,
+        frequency: Hertz,
+        clocks: Clocks,
+    ) -> Result<I2cDevice<'_, I2C, PINS>, FrequencyError> {
+        Ok(I2cDevice {
+            bus: self,
+            speed: prescaler(frequency, &clocks)?,
+        })
+    }
+
+    /// Releases the I2C bus
+    pub fn release(self) -> I2c<I2C, PINS> {
+        self.bus.into_inner()
+    }
+}
+
+/// I2C shared device abstraction
+pub struct I2cDevice<'bus, I2C, PINS> {
+    bus: &'bus SharedI2c<I2C, PINS>,
+    speed: (u32, Hertz),
+}
+
+impl<I2C, PINS> I2cDevice<'_, I2C, PINS> {
+    /// Returns the bus frequency used by this device.
+    pub fn frequency(&self) -> Hertz {
+        self.speed.1
+    }
+}
+
+impl<I2C: I2cX, PINS> ErrorType for I2cDevice<'_, I2C, PINS> {
+    type Error = Error;
+}
+
+impl<I2C: I2cX, PINS> embedded_hal::i2c::I2c for I2cDevice<'_, I2C, PINS> {
+    fn transaction(
+        &mut self,
+        address: u8,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let mut bus = interrupt::free(|| self.bus.bus.try_borrow_mut().map_err(|_| Error::Busy))?;
+        bus.set_prescaler(self.speed.0, self.speed.1);
+        bus.transaction(address, operations)
+    }
+}
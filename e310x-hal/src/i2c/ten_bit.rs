@@ -0,0 +1,92 @@
+use embedded_hal::i2c::{self, NoAcknowledgeSource, Operation, TenBitAddress};
+
+use super::{ten_bit_header, Deadline, Error, I2c, I2cX, FLAG_READ, FLAG_WRITE};
+
+impl<I2C: I2cX, PINS> I2c<I2C, PINS> {
+    /// Sends the full 10-bit address in write mode, after a (repeated) START condition.
+    fn address_ten_bit(&self, address: TenBitAddress, deadline: Deadline) -> Result<(), Error> {
+        self.write_txr(ten_bit_header(address) + FLAG_WRITE);
+        self.trigger_write(true, false);
+        self.wait_for_write(NoAcknowledgeSource::Address, deadline)?;
+        self.write_txr(address as u8);
+        self.trigger_write(false, false);
+        self.wait_for_write(NoAcknowledgeSource::Address, deadline)
+    }
+}
+
+impl<I2C: I2cX, PINS> i2c::I2c<TenBitAddress> for I2c<I2C, PINS> {
+    fn transaction(
+        &mut self,
+        address: TenBitAddress,
+        operations: &mut [Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        let n_ops = operations.len();
+        if n_ops == 0 {
+            return Ok(());
+        }
+
+        let deadline = Deadline::new(self.timeout);
+        self.wait_idle(deadline)?;
+        self.reset();
+
+        // `None` before the first operation, `Some(true)` after a read
+        let mut last_op_was_read = None;
+
+        for i in 0..n_ops {
+            // The last byte of adjacent reads is not acknowledged
+            let next_op_is_read = matches!(operations.get(i + 1), Some(Operation::Read(_)));
+            match &mut operations[i] {
+                Operation::Write(bytes) => {
+                    // Adjacent writes are merged
+                    if last_op_was_read != Some(false) {
+                        self.address_ten_bit(address, deadline)?;
+                    }
+                    last_op_was_read = Some(false);
+
+                    // Write bytes
+                    let n_bytes = bytes.len();
+                    for (j, byte) in bytes.iter().enumerate() {
+                        self.write_txr(*byte);
+                        self.trigger_write(false, (i == n_ops - 1) && (j == n_bytes - 1));
+                        self.wait_for_write(NoAcknowledgeSource::Data, deadline)?;
+                    }
+                }
+                Operation::Read(buffer) => {
+                    // Adjacent reads are merged
+                    if last_op_was_read != Some(true) {
+                        // The device must be addressed in write mode before the read header
+                        if last_op_was_read.is_none() {
+                            self.address_ten_bit(address, deadline)?;
+                        }
+                        // Repeated START with the header in read mode
+                        self.write_txr(ten_bit_header(address) + FLAG_READ);
+                        self.trigger_write(true, false);
+                        self.wait_for_write(NoAcknowledgeSource::Address, deadline)?;
+                    }
+                    last_op_was_read = Some(true);
+
+                    // Read bytes
+                    let n_bytes = buffer.len();
+                    for (j, byte) in buffer.iter_mut().enumerate() {
+                        let last_byte = j == n_bytes - 1;
+                        self.trigger_read(
+                            last_byte && !next_op_is_read,
+                            (i == n_ops - 1) && last_byte,
+                        );
+                        self.wait_for_read(deadline)?;
+                        *byte = self.read_rxr();
+                    }
+                }
+            }
+        }
+        // The STOP condition is sent with the last byte, unless there is none
+        let last_op_is_empty = match &operations[n_ops - 1] {
+            Operation::Write(bytes) => bytes.is_empty(),
+            Operation::Read(buffer) => buffer.is_empty(),
+        };
+        if last_op_is_empty {
+            self.set_stop();
+        }
+        self.wait_idle(deadline)
+    }
+}
@@ -48,6 +48,8 @@ pub mod clock;
 pub mod core;
 pub mod delay;
 pub mod device;
+#[cfg(feature = "exception-handler")]
+pub mod exception;
 pub mod gpio;
 pub mod pmu;
 pub mod prelude;
@@ -57,6 +59,8 @@ pub mod serial;
 pub mod spi;
 pub mod stdout;
 pub mod time;
+#[cfg(feature = "trace")]
+pub mod trace;
 pub mod wdog;
 
 #[cfg(feature = "g002")]
@@ -65,4 +69,7 @@ pub mod i2c;
 #[cfg(feature = "async")]
 pub mod asynch;
 
+#[cfg(feature = "framing")]
+pub mod framing;
+
 pub use device::DeviceResources;
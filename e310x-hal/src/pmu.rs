@@ -269,9 +269,12 @@ impl PMUExt for Pmu {
         let ptr_u32 = ptr as *const u32;
         let sliced = core::slice::from_raw_parts(ptr_u32, reg_count);
 
-        backup.backup_iter().enumerate().for_each(|(i, backup_r)| {
-            backup_r.write(|w| w.bits(sliced[i]));
-        });
+        backup
+            .backup_iter()
+            .zip(sliced)
+            .for_each(|(backup_r, value)| {
+                backup_r.write(|w| w.bits(*value));
+            });
 
         Ok(())
     }
@@ -297,9 +300,12 @@ impl PMUExt for Pmu {
         let ptr_u32 = ptr as *mut u32;
         let sliced = core::slice::from_raw_parts_mut(ptr_u32, reg_count);
 
-        backup.backup_iter().enumerate().for_each(|(i, backup_r)| {
-            sliced[i] = backup_r.read().bits();
-        });
+        backup
+            .backup_iter()
+            .zip(sliced)
+            .for_each(|(backup_r, value)| {
+                *value = backup_r.read().bits();
+            });
 
         Ok(())
     }
@@ -12,15 +12,48 @@
 //! - TX: Pin 18 IOF0
 //! - RX: Pin 23 IOF0
 //! - Interrupt::UART1
+//!
+//! # Configuration
+//! [`SerialConfig`] selects the baud rate, stop bits and watermarks, and reports the
+//! achieved baud rate and its error. Use [`Serial::reconfigure`] to change them at runtime.
+//! [`Serial::autobaud`] detects the baud rate of the peer from an incoming `0x55` sync byte.
+//!
+//! # Buffered serial
+//! [`BufferedSerial`] wraps a [`Serial`] instance with interrupt-driven RX and TX ring buffers.
+//! [`BufferedTx`] does the same for a [`Tx`] instance only.
+//!
+//! # Flow control
+//! [`FlowControlSerial`] adds software RTS/CTS flow control to a [`Serial`] instance using two GPIO pins.
+//!
+//! # RS-485
+//! [`Rs485`] wraps a [`Serial`] instance for half-duplex buses, driving the transceiver
+//! driver enable pin around each transmission and discarding the echo of sent bytes.
+//!
+//! # Software serial
+//! [`SoftSerial`] bit-bangs a serial interface on any pair of GPIO pins at up to 115200 baud.
+
+mod autobaud; // contains the automatic baud rate detection
+mod buffered; // contains the interrupt-driven buffered serial abstraction
+mod config; // contains the UART configuration
+mod flow_control; // contains the software RTS/CTS flow control
+mod rs485; // contains the RS-485 half-duplex abstraction
+mod soft; // contains the bit-banged software serial abstraction
 
 pub use crate::spi::{CommType, WatermarkValue};
 use crate::{clock::Clocks, time::Bps};
+pub use autobaud::*;
+pub use buffered::*;
+pub use config::*;
 use core::ops::Deref;
 use e310x::{
     interrupt::{ExternalInterrupt, Priority},
     uart0, Plic, Uart0, Uart1,
 };
 use embedded_hal_nb::serial;
+pub use flow_control::*;
+use riscv::register::mcycle;
+pub use rs485::*;
+pub use soft::*;
 
 /// TX pin
 pub trait TxPin<UART>: private::Sealed {}
@@ -78,6 +111,7 @@ mod impl_uart {
 pub struct Rx<UART, PIN> {
     uart: UART,
     pin: PIN,
+    baud_rate: Bps,
 }
 
 impl<UART, PIN> Rx<UART, PIN> {
@@ -177,6 +211,11 @@ impl<UART: UartX, PIN: RxPin<UART>> Rx<UART, PIN> {
     pub fn is_interrupt_pending(&self) -> bool {
         self.uart.ip().read().rxwm().bit_is_set()
     }
+
+    /// Returns the baud rate achieved by the current UART configuration.
+    pub fn baud_rate(&self) -> Bps {
+        self.baud_rate
+    }
 }
 
 impl<UART: UartX, PIN: RxPin<UART>> serial::ErrorType for Rx<UART, PIN> {
@@ -396,6 +435,16 @@ impl<UART: UartX, PIN: TxPin<UART>> embedded_io::Write for Tx<UART, PIN> {
     }
 }
 
+#[cfg(feature = "ufmt")]
+impl<UART: UartX, PIN: TxPin<UART>> ufmt_write::uWrite for Tx<UART, PIN> {
+    type Error = embedded_io::ErrorKind;
+
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        embedded_io::Write::write_all(self, s.as_bytes())
+    }
+}
+
 /// Serial abstraction
 pub struct Serial<UART, TX, RX> {
     uart: UART,
@@ -405,15 +454,39 @@ pub struct Serial<UART, TX, RX> {
 
 impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> Serial<UART, TX, RX> {
     /// Configures a UART peripheral to provide serial communication
+    ///
+    /// The baud rate divisor is rounded to the closest achievable baud rate.
+    /// Use [`Serial::with_config`] to check the baud rate error against a tolerance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the baud rate divisor does not fit in the `div` register.
     pub fn new(uart: UART, pins: (TX, RX), baud_rate: Bps, clocks: Clocks) -> Self {
-        let div = clocks.tlclk().0 / baud_rate.0 - 1;
-        unsafe {
-            uart.ie().write(|w| w.txwm().bit(false).rxwm().bit(false));
-            uart.div().write(|w| w.bits(div));
-            uart.txctrl()
-                .write(|w| w.counter().bits(1).enable().bit(true));
-            uart.rxctrl().write(|w| w.enable().bit(true));
-        }
+        let config = SerialConfig::new(baud_rate);
+        let baud_rate =
+            config::closest_baud_rate(config.baud_rate, &clocks).expect("baud rate out of range");
+        Self::configured(uart, pins, &config, baud_rate)
+    }
+
+    /// Configures a UART peripheral with the given [`SerialConfig`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the requested baud rate cannot be achieved within the
+    /// configured tolerance. See [`SerialConfig::baud_rate`] for more details.
+    pub fn with_config(
+        uart: UART,
+        pins: (TX, RX),
+        config: &SerialConfig,
+        clocks: Clocks,
+    ) -> Result<Self, ConfigError> {
+        let baud_rate = config.baud_rate(&clocks)?;
+        Ok(Self::configured(uart, pins, config, baud_rate))
+    }
+
+    fn configured(uart: UART, pins: (TX, RX), config: &SerialConfig, baud_rate: BaudRate) -> Self {
+        uart.ie().write(|w| w.txwm().bit(false).rxwm().bit(false));
+        Self::configure(&uart, config, baud_rate.divisor);
 
         let tx = Tx {
             uart: unsafe { UART::steal() },
@@ -422,11 +495,79 @@ impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> Serial<UART, TX, RX> {
         let rx = Rx {
             uart: unsafe { UART::steal() },
             pin: pins.1,
+            baud_rate: Bps(baud_rate.achieved),
         };
 
         Serial { uart, tx, rx }
     }
 
+    /// Writes the divisor, stop bits and watermarks to the UART registers
+    fn configure(uart: &UART, config: &SerialConfig, divisor: u32) {
+        unsafe {
+            uart.div().write(|w| w.bits(divisor));
+            uart.txctrl().write(|w| {
+                w.counter().bits(config.tx_watermark.into());
+                w.nstop().bit(config.stop_bits == StopBits::Two);
+                w.enable().bit(true)
+            });
+            uart.rxctrl().write(|w| {
+                w.counter().bits(config.rx_watermark.into());
+                w.enable().bit(true)
+            });
+        }
+    }
+
+    /// Changes the UART configuration at runtime (e.g., after a [`Clocks`] change)
+    ///
+    /// Pending transmissions are completed with the previous configuration before
+    /// the new one is applied. The pins and the interrupt enable bits are left untouched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the requested baud rate cannot be achieved within the
+    /// configured tolerance. In that case, the UART configuration is not modified.
+    pub fn reconfigure(
+        &mut self,
+        config: &SerialConfig,
+        clocks: Clocks,
+    ) -> Result<BaudRate, ConfigError> {
+        let baud_rate = config.baud_rate(&clocks)?;
+
+        // Wait until the TX FIFO is empty
+        self.tx.set_watermark(WatermarkValue::W1);
+        while !self.tx.is_interrupt_pending() {}
+
+        // Wait for the shift register to send the last frame
+        let frame_cycles = self.frame_cycles();
+        let start = mcycle::read64();
+        while mcycle::read64() - start < frame_cycles {}
+
+        Self::configure(&self.uart, config, baud_rate.divisor);
+        self.rx.baud_rate = Bps(baud_rate.achieved);
+        Ok(baud_rate)
+    }
+
+    /// Returns the duration of a frame in `mcycle` ticks
+    ///
+    /// The UART is clocked by `tlclk`, which is the same as the core clock.
+    fn frame_cycles(&self) -> u64 {
+        let div = self.uart.div().read().bits() as u64;
+        let n_stop = if self.uart.txctrl().read().nstop().bit_is_set() {
+            2
+        } else {
+            1
+        };
+        // Start bit, 8 data bits and stop bits
+        (div + 1) * (9 + n_stop)
+    }
+
+    /// Returns the baud rate achieved by the current UART configuration.
+    ///
+    /// This is the same value as [`Rx::baud_rate`].
+    pub fn baud_rate(&self) -> Bps {
+        self.rx.baud_rate()
+    }
+
     /// Starts listening for an interrupt event
     pub fn listen(self) -> Self {
         self.uart
@@ -0,0 +1,282 @@
+use super::{config, BaudRate, ConfigError, RxPin, Serial, TxPin, UartX};
+use crate::{
+    clock::Clocks,
+    gpio::{pin_input, set_pin_input_en, set_pin_iof_en, PinIndex},
+    time::Bps,
+};
+use core::time::Duration;
+use e310x::Gpio0;
+use portable_atomic::{AtomicU32, AtomicUsize, Ordering};
+use riscv::register::mcycle;
+
+/// Standard baud rates, to be used as candidates for [`Serial::autobaud`]
+pub const STANDARD_BAUD_RATES: [Bps; 11] = [
+    Bps(1_200),
+    Bps(2_400),
+    Bps(4_800),
+    Bps(9_600),
+    Bps(19_200),
+    Bps(38_400),
+    Bps(57_600),
+    Bps(115_200),
+    Bps(230_400),
+    Bps(460_800),
+    Bps(921_600),
+];
+
+/// Number of edges of a `0x55` sync byte, from the start bit to the stop bit
+const SYNC_EDGES: usize = 10;
+
+/// Maximum deviation between the measured baud rate and the selected candidate in percent
+const AUTOBAUD_TOLERANCE: u32 = 10;
+
+/// Pin index value for an inactive [`AutobaudState`]
+const NO_PIN: usize = usize::MAX;
+
+/// Errors during automatic baud rate detection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutobaudError {
+    /// No candidate baud rates were provided
+    NoCandidates,
+    /// The line went idle before a single bit could be measured
+    InvalidSyncByte,
+    /// No sync byte was received before the timeout
+    Timeout,
+    /// The measured baud rate (in bps) is not close to any of the candidates
+    NoMatch(u32),
+    /// The selected baud rate cannot be configured
+    Config(ConfigError),
+}
+
+impl From<ConfigError> for AutobaudError {
+    fn from(e: ConfigError) -> Self {
+        Self::Config(e)
+    }
+}
+
+/// Edges of the sync byte, timestamped by the GPIO interrupt handler of the RX pin.
+///
+/// Timestamps are the lower 32 bits of `mcycle`, so intervals are computed with wrapping arithmetic.
+struct AutobaudState {
+    pin_index: AtomicUsize,
+    n_edges: AtomicUsize,
+    first: AtomicU32,
+    last: AtomicU32,
+    shortest: AtomicU32,
+}
+
+impl AutobaudState {
+    const fn new() -> Self {
+        Self {
+            pin_index: AtomicUsize::new(NO_PIN),
+            n_edges: AtomicUsize::new(0),
+            first: AtomicU32::new(0),
+            last: AtomicU32::new(0),
+            shortest: AtomicU32::new(u32::MAX),
+        }
+    }
+}
+
+static AUTOBAUD_STATE: AutobaudState = AutobaudState::new();
+
+/// Services the GPIO interrupt of a pin during a baud rate detection.
+///
+/// Returns false if no baud rate detection is running on this pin.
+pub(crate) fn on_autobaud_irq(pin_index: usize) -> bool {
+    let now = mcycle::read() as u32;
+    let state = &AUTOBAUD_STATE;
+    if state.pin_index.load(Ordering::Acquire) != pin_index {
+        return false;
+    }
+    let gpio = unsafe { Gpio0::steal() };
+    let pin_mask = 1 << pin_index;
+    let edge = (gpio.rise_ip().read().bits() | gpio.fall_ip().read().bits()) & pin_mask != 0;
+    unsafe {
+        gpio.fall_ip().write(|w| w.bits(pin_mask));
+        gpio.rise_ip().write(|w| w.bits(pin_mask));
+    }
+    if !edge {
+        return true;
+    }
+
+    let n_edges = state.n_edges.load(Ordering::Relaxed);
+    if n_edges == 0 {
+        state.first.store(now, Ordering::Relaxed);
+    } else {
+        let interval = now.wrapping_sub(state.last.load(Ordering::Relaxed));
+        state.shortest.fetch_min(interval, Ordering::Relaxed);
+    }
+    state.last.store(now, Ordering::Relaxed);
+    state.n_edges.store(n_edges + 1, Ordering::Release);
+    if n_edges + 1 >= SYNC_EDGES {
+        // The whole sync byte was measured, ignore the remaining edges
+        unsafe {
+            gpio.fall_ie().modify(|r, w| w.bits(r.bits() & !pin_mask));
+            gpio.rise_ie().modify(|r, w| w.bits(r.bits() & !pin_mask));
+        }
+    }
+    true
+}
+
+/// Interrupt handler for [`Serial::autobaud`].
+///
+/// This function must be called from the GPIO interrupt handler of the RX pin.
+/// It does nothing if no baud rate detection is running.
+///
+/// # Note
+///
+/// With the `async` feature, the HAL already calls this function from its own
+/// GPIO interrupt handlers.
+pub fn on_autobaud_interrupt() {
+    let pin_index = AUTOBAUD_STATE.pin_index.load(Ordering::Acquire);
+    if pin_index != NO_PIN {
+        on_autobaud_irq(pin_index);
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART> + PinIndex> Serial<UART, TX, RX> {
+    /// Detects the baud rate of the peer from an incoming `0x55` (`'U'`) sync byte.
+    ///
+    /// The RX pin is temporarily switched to GPIO input, and the edges of the sync byte are
+    /// timestamped with `mcycle` by the GPIO edge interrupt of the pin. The closest baud rate
+    /// among `candidates` (e.g., [`STANDARD_BAUD_RATES`]) is then programmed in the `div`
+    /// register, and the RX pin is handed back to the UART.
+    ///
+    /// This method blocks until the sync byte is received or `timeout` expires.
+    /// Other sync bytes work as long as they contain an isolated bit, but `0x55` gives
+    /// the best accuracy.
+    ///
+    /// # Interrupt handling
+    ///
+    /// Interrupts must be enabled, and so must the GPIO interrupt source of the RX pin in
+    /// the PLIC. Its handler must call [`on_autobaud_interrupt`]. When the `async` feature
+    /// is enabled, the HAL already provides the GPIO interrupt handlers and forwards the
+    /// interrupt to the baud rate detection. Other interrupts delay the timestamps, so
+    /// they should not run for longer than a fraction of a bit during the detection.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AutobaudError::Timeout`] if no sync byte is received within `timeout`,
+    /// and an error if the measured baud rate does not match any candidate within 10%,
+    /// or if the selected baud rate cannot be configured. In these cases, the UART
+    /// configuration is not modified.
+    pub fn autobaud(
+        &mut self,
+        clocks: Clocks,
+        candidates: &[Bps],
+        timeout: Duration,
+    ) -> Result<BaudRate, AutobaudError> {
+        let slowest = candidates
+            .iter()
+            .map(|c| c.0)
+            .min()
+            .ok_or(AutobaudError::NoCandidates)?;
+        let coreclk = clocks.coreclk().0;
+        // 0x55 toggles the line every bit, so a gap of two bits at the slowest baud rate ends the byte
+        let max_gap = 2 * (coreclk / slowest.max(1));
+        let timeout_cycles = timeout.as_nanos() * coreclk as u128 / 1_000_000_000;
+        let deadline = mcycle::read64().saturating_add(timeout_cycles.min(u64::MAX as u128) as u64);
+
+        let edges = Self::sync_edges(max_gap, deadline).ok_or(AutobaudError::Timeout)?;
+        let bit_cycles = match edges {
+            (SYNC_EDGES, first, last, _) => last.wrapping_sub(first) / (SYNC_EDGES as u32 - 1),
+            (n, _, _, shortest) if n >= 2 => shortest,
+            _ => return Err(AutobaudError::InvalidSyncByte),
+        };
+        let measured = coreclk / bit_cycles.max(1);
+
+        // Pick the candidate with the lowest relative error
+        let (candidate, error) = candidates
+            .iter()
+            .map(|c| (c.0, measured.abs_diff(c.0) as u64 * 100 / c.0.max(1) as u64))
+            .min_by_key(|(_, error)| *error)
+            .unwrap();
+        if error > AUTOBAUD_TOLERANCE as u64 {
+            return Err(AutobaudError::NoMatch(measured));
+        }
+
+        let baud_rate = config::closest_baud_rate(candidate, &clocks)?;
+        unsafe { self.uart.div().write(|w| w.bits(baud_rate.divisor)) };
+        self.rx.baud_rate = Bps(baud_rate.achieved);
+        Ok(baud_rate)
+    }
+
+    /// Timestamps the edges of the sync byte on the RX pin, giving up at `deadline` (in `mcycle` ticks).
+    ///
+    /// Returns the number of edges and the timestamps of the first and last edges,
+    /// as well as the shortest interval between consecutive edges.
+    fn sync_edges(max_gap: u32, deadline: u64) -> Option<(usize, u32, u32, u32)> {
+        let expired = || mcycle::read64() >= deadline;
+        let gpio = unsafe { Gpio0::steal() };
+        let pin_mask = 1 << RX::INDEX;
+        let input_en = gpio.input_en().read().bits() & pin_mask != 0;
+        let state = &AUTOBAUD_STATE;
+
+        // Hand the pin to the GPIO block, the line must be idle (high)
+        set_pin_input_en(RX::INDEX, true);
+        set_pin_iof_en(RX::INDEX, false);
+        let mut res = None;
+        while !pin_input(RX::INDEX) && !expired() {}
+        if !expired() {
+            state.n_edges.store(0, Ordering::Relaxed);
+            state.shortest.store(u32::MAX, Ordering::Relaxed);
+            state.pin_index.store(RX::INDEX, Ordering::Release);
+            unsafe {
+                gpio.fall_ip().write(|w| w.bits(pin_mask));
+                gpio.rise_ip().write(|w| w.bits(pin_mask));
+                gpio.fall_ie().modify(|r, w| w.bits(r.bits() | pin_mask));
+                gpio.rise_ie().modify(|r, w| w.bits(r.bits() | pin_mask));
+            }
+
+            // The interrupt handler timestamps the edges until the line stays idle for `max_gap`
+            loop {
+                let n_edges = state.n_edges.load(Ordering::Acquire);
+                if n_edges >= SYNC_EDGES {
+                    break;
+                }
+                if n_edges == 0 {
+                    if expired() {
+                        break;
+                    }
+                    continue;
+                }
+                let last = state.last.load(Ordering::Relaxed);
+                let now = mcycle::read() as u32;
+                if now.wrapping_sub(last) > max_gap
+                    && state.n_edges.load(Ordering::Acquire) == n_edges
+                {
+                    break;
+                }
+            }
+
+            unsafe {
+                gpio.fall_ie().modify(|r, w| w.bits(r.bits() & !pin_mask));
+                gpio.rise_ie().modify(|r, w| w.bits(r.bits() & !pin_mask));
+            }
+            state.pin_index.store(NO_PIN, Ordering::Release);
+            let n_edges = state.n_edges.load(Ordering::Acquire);
+            if n_edges > 0 {
+                res = Some((
+                    n_edges,
+                    state.first.load(Ordering::Relaxed),
+                    state.last.load(Ordering::Relaxed),
+                    state.shortest.load(Ordering::Relaxed),
+                ));
+            }
+        }
+
+        // Wait for the stop bit before handing the pin back to the UART
+        while !pin_input(RX::INDEX) && !expired() {}
+        set_pin_iof_en(RX::INDEX, true);
+        set_pin_input_en(RX::INDEX, input_en);
+        unsafe {
+            gpio.fall_ip().write(|w| w.bits(pin_mask));
+            gpio.rise_ip().write(|w| w.bits(pin_mask));
+        }
+
+        // Discard whatever the UART received while the pin was disconnected
+        let rx = unsafe { UART::steal() };
+        while rx.rxdata().read().empty().bit_is_clear() {}
+        res
+    }
+}
@@ -0,0 +1,608 @@
+//! Interrupt-driven buffered serial interface
+//!
+//! [`BufferedSerial`] wraps a [`Serial`] instance and two caller-provided ring buffers.
+//! The UART external interrupt drains the hardware RX FIFO into the RX ring buffer
+//! and refills the hardware TX FIFO from the TX ring buffer, so the application
+//! does not lose bytes while it is busy doing something else.
+//!
+//! [`BufferedTx`] only buffers the transmitter, leaving the receiver to a regular [`Rx`] instance.
+//!
+//! # Interrupt handling
+//!
+//! The UART interrupt handler must call [`on_buffered_interrupt`]. When the `async`
+//! feature is enabled, the HAL already provides the UART interrupt handlers and
+//! forwards the interrupt to the buffered serial interface if it is active.
+//!
+//! # Example
+//!```ignore
+//! static mut RX_BUF: [u8; 64] = [0; 64];
+//! static mut TX_BUF: [u8; 64] = [0; 64];
+//!
+//! let serial = Serial::new(p.UART0, (tx, rx), 115_200.bps(), clocks);
+//! let (rx_buf, tx_buf) = unsafe { (&mut *addr_of_mut!(RX_BUF), &mut *addr_of_mut!(TX_BUF)) };
+//! let mut serial = BufferedSerial::new(serial, rx_buf, tx_buf);
+//! unsafe { serial.enable_exti(&plic) };
+//!
+//! #[riscv_rt::external_interrupt(ExternalInterrupt::UART0)]
+//! fn uart0_handler() {
+//!     on_buffered_interrupt::<Uart0>();
+//! }
+//!```
+
+use super::{RxPin, Serial, Tx, TxPin, UartX, WatermarkValue};
+use e310x::{interrupt::Priority, Plic};
+use portable_atomic::{AtomicPtr, AtomicU32, AtomicUsize, Ordering};
+
+const N_UARTS: usize = 2;
+
+/// Watermark used for the TX FIFO while the buffered serial interface is active.
+/// The interrupt fires when the FIFO holds less than this number of bytes.
+const TX_WATERMARK: WatermarkValue = WatermarkValue::W4;
+
+/// Single-producer single-consumer lock-free ring buffer over a `'static` byte slice.
+///
+/// Read and write indices wrap at `2 * len` so a full buffer can be told apart from an empty one.
+pub(crate) struct RingBuffer {
+    buf: AtomicPtr<u8>,
+    len: AtomicUsize,
+    start: AtomicUsize,
+    end: AtomicUsize,
+}
+
+impl RingBuffer {
+    /// Creates a new ring buffer without backing storage.
+    pub(crate) const fn new() -> Self {
+        Self {
+            buf: AtomicPtr::new(core::ptr::null_mut()),
+            len: AtomicUsize::new(0),
+            start: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+        }
+    }
+
+    /// Attaches the backing storage to the ring buffer and empties it.
+    ///
+    /// # Safety
+    ///
+    /// `buf` must be valid for reads and writes of `len` bytes until [`Self::deinit`] is called.
+    pub(crate) unsafe fn init(&self, buf: *mut u8, len: usize) {
+        self.start.store(0, Ordering::Relaxed);
+        self.end.store(0, Ordering::Relaxed);
+        self.len.store(len, Ordering::Relaxed);
+        self.buf.store(buf, Ordering::Release);
+    }
+
+    /// Detaches the backing storage from the ring buffer and returns it.
+    pub(crate) fn deinit(&self) -> (*mut u8, usize) {
+        let buf = self.buf.swap(core::ptr::null_mut(), Ordering::AcqRel);
+        let len = self.len.swap(0, Ordering::Relaxed);
+        (buf, len)
+    }
+
+    /// Returns true if the ring buffer has backing storage attached.
+    pub(crate) fn is_available(&self) -> bool {
+        !self.buf.load(Ordering::Acquire).is_null()
+    }
+
+    /// Returns the capacity of the ring buffer.
+    pub(crate) fn capacity(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes currently stored in the ring buffer.
+    pub(crate) fn len(&self) -> usize {
+        let cap = self.capacity();
+        let start = self.start.load(Ordering::Acquire);
+        let end = self.end.load(Ordering::Acquire);
+        if end >= start {
+            end - start
+        } else {
+            2 * cap + end - start
+        }
+    }
+
+    /// Returns true if the ring buffer is empty.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if the ring buffer is full.
+    pub(crate) fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    #[inline]
+    fn wrap(index: usize, cap: usize) -> usize {
+        if index >= 2 * cap {
+            index - 2 * cap
+        } else {
+            index
+        }
+    }
+
+    /// Pushes a byte to the ring buffer. Returns false if the buffer is full.
+    ///
+    /// Only one context may act as producer at a time.
+    pub(crate) fn push(&self, byte: u8) -> bool {
+        let buf = self.buf.load(Ordering::Acquire);
+        let cap = self.capacity();
+        if buf.is_null() || self.is_full() {
+            return false;
+        }
+        let end = self.end.load(Ordering::Relaxed);
+        // SAFETY: the index is within the bounds of the backing storage
+        unsafe { buf.add(end % cap).write_volatile(byte) };
+        self.end.store(Self::wrap(end + 1, cap), Ordering::Release);
+        true
+    }
+
+    /// Pops a byte from the ring buffer. Returns `None` if the buffer is empty.
+    ///
+    /// Only one context may act as consumer at a time.
+    pub(crate) fn pop(&self) -> Option<u8> {
+        let buf = self.buf.load(Ordering::Acquire);
+        let cap = self.capacity();
+        if buf.is_null() || self.is_empty() {
+            return None;
+        }
+        let start = self.start.load(Ordering::Relaxed);
+        // SAFETY: the index is within the bounds of the backing storage
+        let byte = unsafe { buf.add(start % cap).read_volatile() };
+        self.start
+            .store(Self::wrap(start + 1, cap), Ordering::Release);
+        Some(byte)
+    }
+
+    /// Pushes as many bytes of `data` as fit in the ring buffer and returns how many were pushed.
+    pub(crate) fn push_slice(&self, data: &[u8]) -> usize {
+        data.iter().take_while(|&&byte| self.push(byte)).count()
+    }
+
+    /// Pops as many bytes as available into `data` and returns how many were popped.
+    pub(crate) fn pop_slice(&self, data: &mut [u8]) -> usize {
+        let mut count = 0;
+        for byte in data.iter_mut() {
+            match self.pop() {
+                Some(b) => {
+                    *byte = b;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        count
+    }
+}
+
+/// Shared state between a [`BufferedSerial`] instance and its interrupt handler.
+pub(crate) struct BufferedState {
+    pub(crate) rx: RingBuffer,
+    pub(crate) tx: RingBuffer,
+    rx_overruns: AtomicU32,
+}
+
+impl BufferedState {
+    const fn new() -> Self {
+        Self {
+            rx: RingBuffer::new(),
+            tx: RingBuffer::new(),
+            rx_overruns: AtomicU32::new(0),
+        }
+    }
+}
+
+pub(crate) static BUFFERED_STATE: [BufferedState; N_UARTS] =
+    [const { BufferedState::new() }; N_UARTS];
+
+/// Events processed by the buffered serial interrupt handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct BufferedEvents {
+    /// New bytes were stored in the RX ring buffer
+    pub(crate) rx: bool,
+    /// Bytes were moved from the TX ring buffer to the TX FIFO
+    pub(crate) tx: bool,
+}
+
+/// Services the UART interrupt for a buffered serial interface.
+///
+/// Returns `None` if no [`BufferedSerial`] nor [`BufferedTx`] instance is active for this UART.
+pub(crate) fn on_buffered_irq<UART: UartX>(uart: &UART) -> Option<BufferedEvents> {
+    let state = &BUFFERED_STATE[UART::UART_INDEX];
+    if !state.rx.is_available() && !state.tx.is_available() {
+        return None;
+    }
+    let mut events = BufferedEvents::default();
+
+    // Drain the RX FIFO into the ring buffer
+    if state.rx.is_available() && uart.ie().read().rxwm().bit_is_set() {
+        loop {
+            let rxdata = uart.rxdata().read();
+            if rxdata.empty().bit_is_set() {
+                break;
+            }
+            if state.rx.push(rxdata.data().bits()) {
+                events.rx = true;
+            } else {
+                state.rx_overruns.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // Refill the TX FIFO from the ring buffer
+    if uart.ie().read().txwm().bit_is_set() && uart.ip().read().txwm().bit_is_set() {
+        while uart.txdata().read().full().bit_is_clear() {
+            match state.tx.pop() {
+                Some(byte) => {
+                    uart.txdata().write(|w| unsafe { w.data().bits(byte) });
+                    events.tx = true;
+                }
+                None => {
+                    // Nothing left to send, stop listening until new data is written
+                    uart.ie().modify(|r, w| {
+                        w.rxwm().bit(r.rxwm().bit());
+                        w.txwm().clear_bit()
+                    });
+                    events.tx = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    Some(events)
+}
+
+/// Interrupt handler for [`BufferedSerial`] and [`BufferedTx`].
+///
+/// This function must be called from the UART external interrupt handler.
+/// It does nothing if no [`BufferedSerial`] nor [`BufferedTx`] instance is active for this UART.
+///
+/// # Note
+///
+/// With the `async` feature, the HAL already calls this function from its own
+/// UART interrupt handlers.
+pub fn on_buffered_interrupt<UART: UartX>() {
+    let uart = unsafe { UART::steal() };
+    on_buffered_irq(&uart);
+}
+
+/// Interrupt-driven serial interface with software RX and TX ring buffers
+pub struct BufferedSerial<UART, TX, RX> {
+    serial: Serial<UART, TX, RX>,
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> BufferedSerial<UART, TX, RX> {
+    /// Creates a buffered serial interface from a [`Serial`] instance and two ring buffers.
+    ///
+    /// The RX watermark interrupt is enabled right away, so incoming bytes are stored
+    /// in `rx_buf` as soon as the UART external interrupt is enabled in the PLIC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the buffers is empty.
+    pub fn new(
+        mut serial: Serial<UART, TX, RX>,
+        rx_buf: &'static mut [u8],
+        tx_buf: &'static mut [u8],
+    ) -> Self {
+        assert!(!rx_buf.is_empty() && !tx_buf.is_empty());
+
+        serial.disable_interrupt(super::CommType::TxRx);
+        let state = &BUFFERED_STATE[UART::UART_INDEX];
+        state.rx_overruns.store(0, Ordering::Relaxed);
+        // SAFETY: the buffers are 'static and exclusively owned by the state until `free`
+        unsafe {
+            state.tx.init(tx_buf.as_mut_ptr(), tx_buf.len());
+            state.rx.init(rx_buf.as_mut_ptr(), rx_buf.len());
+        }
+
+        serial.set_watermark(super::CommType::Rx, WatermarkValue::W0);
+        serial.set_watermark(super::CommType::Tx, TX_WATERMARK);
+        serial.enable_interrupt(super::CommType::Rx);
+
+        Self { serial }
+    }
+
+    /// Disables the UART interrupts and releases the [`Serial`] instance and the ring buffers.
+    ///
+    /// Any data still stored in the ring buffers is discarded.
+    pub fn free(mut self) -> (Serial<UART, TX, RX>, &'static mut [u8], &'static mut [u8]) {
+        self.serial.disable_interrupt(super::CommType::TxRx);
+        let state = &BUFFERED_STATE[UART::UART_INDEX];
+        let (rx_ptr, rx_len) = state.rx.deinit();
+        let (tx_ptr, tx_len) = state.tx.deinit();
+        // SAFETY: the pointers come from the 'static buffers provided in `new`
+        let (rx_buf, tx_buf) = unsafe {
+            (
+                core::slice::from_raw_parts_mut(rx_ptr, rx_len),
+                core::slice::from_raw_parts_mut(tx_ptr, tx_len),
+            )
+        };
+        (self.serial, rx_buf, tx_buf)
+    }
+
+    /// Enables the external interrupt source for the UART.
+    ///
+    /// # Note
+    /// This function enables the external interrupt source in the PLIC,
+    /// but does not enable the PLIC peripheral itself. To enable the plic peripheral
+    /// you must call [`Plic::enable()`](riscv-peripheral::plic::enables::ENABLES::enable).
+    ///
+    /// # Safety
+    /// Enabling an interrupt source can break mask-based critical sections.
+    pub unsafe fn enable_exti(&mut self, plic: &Plic) {
+        self.serial.enable_exti(plic);
+    }
+
+    /// Disables the external interrupt source for the UART.
+    pub fn disable_exti(&mut self, plic: &Plic) {
+        self.serial.disable_exti(plic);
+    }
+
+    /// Returns whether the external interrupt source for the UART is enabled.
+    pub fn is_exti_enabled(&self, plic: &Plic) -> bool {
+        self.serial.is_exti_enabled(plic)
+    }
+
+    /// Sets the external interrupt source priority.
+    ///
+    /// # Safety
+    ///
+    /// Changing the priority level can break priority-based critical sections.
+    pub unsafe fn set_exti_priority(&mut self, plic: &Plic, priority: Priority) {
+        self.serial.set_exti_priority(plic, priority);
+    }
+
+    /// Returns the external interrupt source priority.
+    pub fn get_exti_priority(&self, plic: &Plic) -> Priority {
+        self.serial.get_exti_priority(plic)
+    }
+
+    /// Returns the number of bytes waiting in the RX ring buffer.
+    pub fn rx_len(&self) -> usize {
+        Self::state().rx.len()
+    }
+
+    /// Returns the number of bytes waiting in the TX ring buffer.
+    pub fn tx_len(&self) -> usize {
+        Self::state().tx.len()
+    }
+
+    /// Returns the number of received bytes dropped because the RX ring buffer was full.
+    pub fn rx_overruns(&self) -> u32 {
+        Self::state().rx_overruns.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of received bytes dropped because the RX ring buffer was full
+    /// and resets the counter.
+    pub fn take_rx_overruns(&mut self) -> u32 {
+        Self::state().rx_overruns.swap(0, Ordering::Relaxed)
+    }
+
+    /// Moves bytes between the ring buffers and the FIFOs without waiting for the interrupt.
+    ///
+    /// The interrupt handler logic runs with interrupts disabled, so this method can be
+    /// used to make progress from critical sections and higher priority handlers.
+    pub fn poll(&mut self) {
+        let uart = unsafe { UART::steal() };
+        riscv::interrupt::free(|| on_buffered_irq(&uart));
+    }
+
+    #[inline]
+    pub(crate) fn state() -> &'static BufferedState {
+        &BUFFERED_STATE[UART::UART_INDEX]
+    }
+
+    /// Pushes as many bytes as possible to the TX ring buffer and starts the transmission.
+    pub(crate) fn push_tx(&mut self, buf: &[u8]) -> usize {
+        let count = Self::state().tx.push_slice(buf);
+        if count > 0 {
+            // The interrupt fires right away if the TX FIFO is below the watermark
+            self.serial.tx.set_watermark(TX_WATERMARK);
+            self.serial.tx.enable_interrupt();
+        }
+        count
+    }
+
+    /// Returns true if the TX FIFO is empty.
+    ///
+    /// The TX watermark is temporarily lowered to check it, so the TX ring buffer must be empty.
+    pub(crate) fn is_tx_fifo_empty(&mut self) -> bool {
+        self.serial.tx.set_watermark(WatermarkValue::W1);
+        let empty = self.serial.tx.is_interrupt_pending();
+        self.serial.tx.set_watermark(TX_WATERMARK);
+        empty
+    }
+
+    /// Returns true if the TX FIFO is empty. Otherwise, enables the TX interrupt,
+    /// which fires once the TX FIFO is empty.
+    ///
+    /// The TX ring buffer must be empty. The TX watermark is restored by the next write.
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_tx_fifo_empty(&mut self) -> bool {
+        if self.is_tx_fifo_empty() {
+            return true;
+        }
+        self.serial.tx.set_watermark(WatermarkValue::W1);
+        self.serial.tx.enable_interrupt();
+        false
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io::ErrorType
+    for BufferedSerial<UART, TX, RX>
+{
+    type Error = embedded_io::ErrorKind;
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io::ReadReady
+    for BufferedSerial<UART, TX, RX>
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!Self::state().rx.is_empty())
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io::Read
+    for BufferedSerial<UART, TX, RX>
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let rx = &Self::state().rx;
+        while rx.is_empty() {
+            // first byte may block
+            self.poll();
+        }
+        Ok(rx.pop_slice(buf))
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io::WriteReady
+    for BufferedSerial<UART, TX, RX>
+{
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!Self::state().tx.is_full())
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> embedded_io::Write
+    for BufferedSerial<UART, TX, RX>
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while Self::state().tx.is_full() {
+            // first byte may block
+            self.poll();
+        }
+        Ok(self.push_tx(buf))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while !Self::state().tx.is_empty() {
+            self.poll();
+        }
+        while !self.is_tx_fifo_empty() {}
+        Ok(())
+    }
+}
+
+/// Interrupt-driven transmitter with a software TX ring buffer
+///
+/// Writes copy the data to the ring buffer and return right away, while the UART
+/// interrupt handler refills the TX FIFO in the background.
+pub struct BufferedTx<UART, PIN> {
+    tx: Tx<UART, PIN>,
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> BufferedTx<UART, PIN> {
+    /// Creates a buffered transmitter from a [`Tx`] instance and a ring buffer.
+    ///
+    /// The UART external interrupt must be enabled in the PLIC for the data to be sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is empty.
+    pub fn new(mut tx: Tx<UART, PIN>, buf: &'static mut [u8]) -> Self {
+        assert!(!buf.is_empty());
+
+        tx.disable_interrupt();
+        // SAFETY: the buffer is 'static and exclusively owned by the state until `free`
+        unsafe {
+            BUFFERED_STATE[UART::UART_INDEX]
+                .tx
+                .init(buf.as_mut_ptr(), buf.len())
+        };
+        tx.set_watermark(TX_WATERMARK);
+
+        Self { tx }
+    }
+
+    /// Disables the TX interrupt and releases the [`Tx`] instance and the ring buffer.
+    ///
+    /// Any data still stored in the ring buffer is discarded.
+    pub fn free(mut self) -> (Tx<UART, PIN>, &'static mut [u8]) {
+        self.tx.disable_interrupt();
+        let (ptr, len) = BUFFERED_STATE[UART::UART_INDEX].tx.deinit();
+        // SAFETY: the pointer comes from the 'static buffer provided in `new`
+        let buf = unsafe { core::slice::from_raw_parts_mut(ptr, len) };
+        (self.tx, buf)
+    }
+
+    /// Enables the external interrupt source for the UART.
+    ///
+    /// # Safety
+    /// Enabling an interrupt source can break mask-based critical sections.
+    pub unsafe fn enable_exti(&mut self, plic: &Plic) {
+        self.tx.enable_exti(plic);
+    }
+
+    /// Disables the external interrupt source for the UART.
+    pub fn disable_exti(&mut self, plic: &Plic) {
+        self.tx.disable_exti(plic);
+    }
+
+    /// Sets the external interrupt source priority.
+    ///
+    /// # Safety
+    ///
+    /// Changing the priority level can break priority-based critical sections.
+    pub unsafe fn set_exti_priority(&mut self, plic: &Plic, priority: Priority) {
+        self.tx.set_exti_priority(plic, priority);
+    }
+
+    /// Returns the number of bytes waiting in the TX ring buffer.
+    pub fn tx_len(&self) -> usize {
+        BUFFERED_STATE[UART::UART_INDEX].tx.len()
+    }
+
+    /// Moves bytes from the TX ring buffer to the TX FIFO without waiting for the interrupt.
+    ///
+    /// The interrupt handler logic runs with interrupts disabled, so this method can be
+    /// used to make progress from critical sections and higher priority handlers.
+    pub fn poll_tx(&mut self) {
+        let uart = unsafe { UART::steal() };
+        riscv::interrupt::free(|| on_buffered_irq(&uart));
+    }
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> embedded_io::ErrorType for BufferedTx<UART, PIN> {
+    type Error = embedded_io::ErrorKind;
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> embedded_io::WriteReady for BufferedTx<UART, PIN> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!BUFFERED_STATE[UART::UART_INDEX].tx.is_full())
+    }
+}
+
+impl<UART: UartX, PIN: TxPin<UART>> embedded_io::Write for BufferedTx<UART, PIN> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let ring = &BUFFERED_STATE[UART::UART_INDEX].tx;
+        while ring.is_full() {
+            // first byte may block
+            self.poll_tx();
+        }
+        let count = ring.push_slice(buf);
+        // The interrupt fires right away if the TX FIFO is below the watermark
+        self.tx.enable_interrupt();
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        while !BUFFERED_STATE[UART::UART_INDEX].tx.is_empty() {
+            self.poll_tx();
+        }
+        // The watermark is pending once the TX FIFO is empty
+        self.tx.set_watermark(WatermarkValue::W1);
+        while !self.tx.is_interrupt_pending() {}
+        self.tx.set_watermark(TX_WATERMARK);
+        Ok(())
+    }
+}
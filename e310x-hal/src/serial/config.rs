@@ -0,0 +1,109 @@
+use super::WatermarkValue;
+use crate::{clock::Clocks, time::Bps};
+
+/// Maximum value of the baud rate divisor register
+const DIV_MAX: u32 = 0xffff;
+
+/// Number of stop bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopBits {
+    /// One stop bit
+    One,
+    /// Two stop bits
+    Two,
+}
+
+/// Errors when computing the UART configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The requested baud rate is higher than the TileLink bus clock
+    BaudRateTooHigh,
+    /// The requested baud rate needs a divisor that does not fit in the `div` register
+    BaudRateTooLow,
+    /// The achievable baud rate deviates from the requested one more than the configured tolerance
+    BaudRateError(BaudRate),
+}
+
+/// Baud rate achieved by a given divisor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaudRate {
+    /// Value for the `div` register
+    pub divisor: u32,
+    /// Achieved baud rate
+    pub achieved: u32,
+    /// Deviation from the requested baud rate in parts per million
+    pub error_ppm: i32,
+}
+
+/// UART configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerialConfig {
+    /// Requested baud rate
+    pub baud_rate: u32,
+    /// Number of stop bits
+    pub stop_bits: StopBits,
+    /// Watermark level for transmits
+    pub tx_watermark: WatermarkValue,
+    /// Watermark level for received
+    pub rx_watermark: WatermarkValue,
+    /// Maximum allowed baud rate error in parts per million
+    pub tolerance_ppm: u32,
+}
+
+impl SerialConfig {
+    /// Default maximum allowed baud rate error in parts per million (2%)
+    pub const DEFAULT_TOLERANCE_PPM: u32 = 20_000;
+
+    /// Create new default configuration with the given baud rate
+    ///
+    /// By default, one stop bit is used, the TX watermark is 1, the RX watermark is 0,
+    /// and the baud rate error tolerance is [`Self::DEFAULT_TOLERANCE_PPM`].
+    pub fn new(baud_rate: Bps) -> Self {
+        Self {
+            baud_rate: baud_rate.0,
+            stop_bits: StopBits::One,
+            tx_watermark: WatermarkValue::W1,
+            rx_watermark: WatermarkValue::W0,
+            tolerance_ppm: Self::DEFAULT_TOLERANCE_PPM,
+        }
+    }
+
+    /// Computes the closest divisor for the requested baud rate using the core [Clocks]
+    ///
+    /// # Errors
+    ///
+    /// * [`ConfigError::BaudRateTooHigh`] - the baud rate is higher than the `tlclk` frequency
+    /// * [`ConfigError::BaudRateTooLow`] - the divisor does not fit in the `div` register
+    /// * [`ConfigError::BaudRateError`] - the baud rate error exceeds the configured tolerance
+    pub fn baud_rate(&self, clocks: &Clocks) -> Result<BaudRate, ConfigError> {
+        let baud_rate = closest_baud_rate(self.baud_rate, clocks)?;
+        match baud_rate.error_ppm.unsigned_abs() <= self.tolerance_ppm {
+            true => Ok(baud_rate),
+            false => Err(ConfigError::BaudRateError(baud_rate)),
+        }
+    }
+}
+
+/// Computes the closest divisor for the requested baud rate, regardless of the resulting error
+pub(crate) fn closest_baud_rate(baud_rate: u32, clocks: &Clocks) -> Result<BaudRate, ConfigError> {
+    let tlclk = clocks.tlclk().0;
+    if baud_rate == 0 {
+        return Err(ConfigError::BaudRateTooLow);
+    }
+    if baud_rate > tlclk {
+        return Err(ConfigError::BaudRateTooHigh);
+    }
+    // baud = tlclk / (div + 1), rounded to the nearest divisor
+    let divisor = (tlclk + baud_rate / 2) / baud_rate - 1;
+    if divisor > DIV_MAX {
+        return Err(ConfigError::BaudRateTooLow);
+    }
+    let achieved = tlclk / (divisor + 1);
+    let error_ppm = (achieved as i64 - baud_rate as i64) * 1_000_000 / baud_rate as i64;
+
+    Ok(BaudRate {
+        divisor,
+        achieved,
+        error_ppm: error_ppm as i32,
+    })
+}
@@ -0,0 +1,325 @@
+//! Software RTS/CTS hardware flow control
+//!
+//! The FE310 UARTs have no flow control lines, so [`FlowControlSerial`] drives them
+//! with two GPIO pins. Both lines are active low on the wire, regardless of whether the
+//! RTS pin is configured as an inverted output:
+//!
+//! - RTS (output) is asserted while the RX FIFO holds up to a configurable threshold.
+//!   The UART interrupt releases it as soon as the threshold is exceeded,
+//!   and reads assert it again once they drain the FIFO.
+//! - CTS (input) is checked before each TX FIFO refill. Asynchronous writes wait for
+//!   the falling edge of CTS to resume, while blocking writes spin on it.
+//!
+//! Note that the bytes already pushed to the TX FIFO are sent even if CTS is released.
+//!
+//! # Interrupt handling
+//!
+//! The UART interrupt handler must call [`on_flow_control_interrupt`]. When the `async`
+//! feature is enabled, the HAL already provides the UART and GPIO interrupt handlers and
+//! forwards the interrupts to the flow control logic if it is active.
+//!
+//! # Example
+//!```ignore
+//! let serial = Serial::new(p.UART0, (tx, rx), 115_200.bps(), clocks);
+//! let rts = pins.pin10.into_output();
+//! let cts = pins.pin11.into_pull_up_input();
+//! let mut serial = FlowControlSerial::new(serial, rts, cts, WatermarkValue::W4);
+//! unsafe { serial.enable_exti(&plic) };
+//!
+//! #[riscv_rt::external_interrupt(ExternalInterrupt::UART0)]
+//! fn uart0_handler() {
+//!     on_flow_control_interrupt::<Uart0>();
+//! }
+//!```
+
+use super::{RxPin, Serial, TxPin, UartX, WatermarkValue};
+use crate::gpio::{pin_input, pin_level, set_pin_level, PinIndex};
+use e310x::{interrupt::Priority, Plic};
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_nb::serial;
+use portable_atomic::{AtomicU32, AtomicUsize, Ordering};
+
+const N_UARTS: usize = 2;
+
+/// Pin index value for an inactive flow control state
+const NO_PIN: usize = usize::MAX;
+
+/// Flow control state shared with the UART interrupt handler.
+pub(crate) struct FlowControlState {
+    pub(crate) rts: AtomicUsize,
+    pub(crate) cts: AtomicUsize,
+    threshold: AtomicU32,
+}
+
+impl FlowControlState {
+    const fn new() -> Self {
+        Self {
+            rts: AtomicUsize::new(NO_PIN),
+            cts: AtomicUsize::new(NO_PIN),
+            threshold: AtomicU32::new(0),
+        }
+    }
+}
+
+pub(crate) static FLOW_CONTROL_STATE: [FlowControlState; N_UARTS] =
+    [const { FlowControlState::new() }; N_UARTS];
+
+/// Processes the RX watermark interrupt of a UART with flow control.
+///
+/// Returns `None` if no [`FlowControlSerial`] instance is active for this UART.
+/// Otherwise, returns whether new received data is available.
+/// The TX watermark interrupt is left to the caller.
+pub(crate) fn on_flow_control_irq<UART: UartX>(uart: &UART) -> Option<bool> {
+    let state = &FLOW_CONTROL_STATE[UART::UART_INDEX];
+    let rts = state.rts.load(Ordering::Acquire);
+    if rts == NO_PIN {
+        return None;
+    }
+    if uart.ie().read().rxwm().bit_is_clear() || uart.ip().read().rxwm().bit_is_clear() {
+        return Some(false);
+    }
+
+    let threshold = state.threshold.load(Ordering::Relaxed) as u8;
+    if uart.rxctrl().read().counter().bits() < threshold {
+        // A reader waits for the first byte, keep monitoring the threshold
+        uart.rxctrl().modify(|r, w| {
+            w.enable().bit(r.enable().bit());
+            unsafe { w.counter().bits(threshold) }
+        });
+    } else {
+        // The RX FIFO exceeds the threshold, release RTS until the next read
+        set_pin_level(rts, true);
+        uart.ie().modify(|r, w| {
+            w.txwm().bit(r.txwm().bit());
+            w.rxwm().clear_bit()
+        });
+    }
+    Some(true)
+}
+
+/// Interrupt handler for [`FlowControlSerial`].
+///
+/// This function must be called from the UART external interrupt handler.
+/// It does nothing if no [`FlowControlSerial`] instance is active for this UART.
+///
+/// # Note
+///
+/// With the `async` feature, the HAL already calls this function from its own
+/// UART interrupt handlers.
+pub fn on_flow_control_interrupt<UART: UartX>() {
+    let uart = unsafe { UART::steal() };
+    on_flow_control_irq(&uart);
+}
+
+/// Serial interface with software RTS/CTS flow control
+pub struct FlowControlSerial<UART, TX, RX, RTS, CTS> {
+    pub(crate) serial: Serial<UART, TX, RX>,
+    rts: RTS,
+    cts: CTS,
+}
+
+impl<UART, TX, RX, RTS, CTS> FlowControlSerial<UART, TX, RX, RTS, CTS>
+where
+    UART: UartX,
+    TX: TxPin<UART>,
+    RX: RxPin<UART>,
+    RTS: OutputPin + PinIndex,
+    CTS: InputPin + PinIndex,
+{
+    /// Adds RTS/CTS flow control to a [`Serial`] instance.
+    ///
+    /// RTS is released when the RX FIFO holds more than `threshold` bytes.
+    /// The RX watermark interrupt is enabled right away, but the UART external
+    /// interrupt must be enabled in the PLIC to release RTS on time.
+    pub fn new(
+        mut serial: Serial<UART, TX, RX>,
+        rts: RTS,
+        cts: CTS,
+        threshold: WatermarkValue,
+    ) -> Self {
+        serial.disable_interrupt(super::CommType::TxRx);
+        let state = &FLOW_CONTROL_STATE[UART::UART_INDEX];
+        state
+            .threshold
+            .store(u8::from(threshold) as u32, Ordering::Relaxed);
+        state.cts.store(CTS::INDEX, Ordering::Relaxed);
+        state.rts.store(RTS::INDEX, Ordering::Release);
+
+        let mut res = Self { serial, rts, cts };
+        res.resume_rx();
+        res
+    }
+
+    /// Disables the flow control and releases the [`Serial`] instance and the RTS and CTS pins
+    ///
+    /// RTS is left asserted.
+    pub fn free(mut self) -> (Serial<UART, TX, RX>, RTS, CTS) {
+        self.serial.disable_interrupt(super::CommType::TxRx);
+        let state = &FLOW_CONTROL_STATE[UART::UART_INDEX];
+        state.rts.store(NO_PIN, Ordering::Release);
+        state.cts.store(NO_PIN, Ordering::Release);
+        (self.serial, self.rts, self.cts)
+    }
+
+    /// Enables the external interrupt source for the UART.
+    ///
+    /// # Safety
+    /// Enabling an interrupt source can break mask-based critical sections.
+    pub unsafe fn enable_exti(&mut self, plic: &Plic) {
+        self.serial.enable_exti(plic);
+    }
+
+    /// Disables the external interrupt source for the UART.
+    pub fn disable_exti(&mut self, plic: &Plic) {
+        self.serial.disable_exti(plic);
+    }
+
+    /// Sets the external interrupt source priority.
+    ///
+    /// # Safety
+    ///
+    /// Changing the priority level can break priority-based critical sections.
+    pub unsafe fn set_exti_priority(&mut self, plic: &Plic, priority: Priority) {
+        self.serial.set_exti_priority(plic, priority);
+    }
+
+    /// Returns true if the peer asserts CTS (i.e., it is ready to receive data).
+    pub fn is_clear_to_send(&self) -> bool {
+        !pin_input(CTS::INDEX)
+    }
+
+    /// Returns true if RTS is asserted (i.e., we are ready to receive data).
+    pub fn is_ready_to_receive(&self) -> bool {
+        // RTS is driven by index from the interrupt handler
+        !pin_level(RTS::INDEX)
+    }
+
+    /// Monitors the RX FIFO threshold again and asserts RTS.
+    ///
+    /// If the RX FIFO still exceeds the threshold, the interrupt handler releases RTS right away.
+    pub(crate) fn resume_rx(&mut self) {
+        let threshold = FLOW_CONTROL_STATE[UART::UART_INDEX]
+            .threshold
+            .load(Ordering::Relaxed) as u8;
+        riscv::interrupt::free(|| {
+            self.serial.rx.set_watermark(threshold.try_into().unwrap());
+            set_pin_level(RTS::INDEX, false);
+            self.serial.rx.enable_interrupt();
+        });
+    }
+
+    /// Moves the contents of the RX FIFO to `buf`, returning the number of bytes read.
+    pub(crate) fn drain_rx(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        for byte in buf.iter_mut() {
+            match serial::Read::read(&mut self.serial.rx) {
+                Ok(b) => {
+                    *byte = b;
+                    count += 1
+                }
+                Err(nb::Error::WouldBlock) => break,
+                _ => unreachable!(),
+            }
+        }
+        count
+    }
+
+    /// Pushes bytes to the TX FIFO until it is full, returning the number of bytes written.
+    pub(crate) fn fill_tx(&mut self, buf: &[u8]) -> usize {
+        let mut count = 0;
+        for byte in buf {
+            match serial::Write::write(&mut self.serial.tx, *byte) {
+                Ok(()) => count += 1,
+                Err(nb::Error::WouldBlock) => break,
+                _ => unreachable!(),
+            }
+        }
+        count
+    }
+}
+
+impl<UART, TX, RX, RTS, CTS> embedded_io::ErrorType for FlowControlSerial<UART, TX, RX, RTS, CTS> {
+    type Error = embedded_io::ErrorKind;
+}
+
+impl<UART, TX, RX, RTS, CTS> embedded_io::ReadReady for FlowControlSerial<UART, TX, RX, RTS, CTS>
+where
+    UART: UartX,
+    TX: TxPin<UART>,
+    RX: RxPin<UART>,
+    RTS: OutputPin + PinIndex,
+    CTS: InputPin + PinIndex,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        // The RX watermark is pending while the FIFO holds more than `counter` bytes
+        let watermark = self.serial.rx.get_watermark();
+        let ready = riscv::interrupt::free(|| {
+            self.serial.rx.set_watermark(WatermarkValue::W0);
+            let ready = self.serial.rx.is_interrupt_pending();
+            self.serial.rx.set_watermark(watermark.try_into().unwrap());
+            ready
+        });
+        Ok(ready)
+    }
+}
+
+impl<UART, TX, RX, RTS, CTS> embedded_io::Read for FlowControlSerial<UART, TX, RX, RTS, CTS>
+where
+    UART: UartX,
+    TX: TxPin<UART>,
+    RX: RxPin<UART>,
+    RTS: OutputPin + PinIndex,
+    CTS: InputPin + PinIndex,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut count = 0;
+        while count == 0 {
+            count = self.drain_rx(buf); // first byte may block
+        }
+        self.resume_rx();
+        Ok(count)
+    }
+}
+
+impl<UART, TX, RX, RTS, CTS> embedded_io::WriteReady for FlowControlSerial<UART, TX, RX, RTS, CTS>
+where
+    UART: UartX,
+    TX: TxPin<UART>,
+    RX: RxPin<UART>,
+    RTS: OutputPin + PinIndex,
+    CTS: InputPin + PinIndex,
+{
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.is_clear_to_send() && embedded_io::WriteReady::write_ready(&mut self.serial.tx)?)
+    }
+}
+
+impl<UART, TX, RX, RTS, CTS> embedded_io::Write for FlowControlSerial<UART, TX, RX, RTS, CTS>
+where
+    UART: UartX,
+    TX: TxPin<UART>,
+    RX: RxPin<UART>,
+    RTS: OutputPin + PinIndex,
+    CTS: InputPin + PinIndex,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            // CTS is checked before each TX FIFO refill
+            while !self.is_clear_to_send() {}
+            match self.fill_tx(buf) {
+                0 => continue,
+                count => return Ok(count),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        embedded_io::Write::flush(&mut self.serial.tx)
+    }
+}
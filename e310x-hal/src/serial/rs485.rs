@@ -0,0 +1,187 @@
+use super::{RxPin, Serial, TxPin, UartX, WatermarkValue};
+use embedded_hal::digital::OutputPin;
+use embedded_hal_nb::serial;
+use riscv::register::mcycle;
+
+/// RS-485 half-duplex serial abstraction
+///
+/// The driver enable (DE/RE) pin of the transceiver is asserted before the first byte
+/// of each write and released right after the stop bit of the last one.
+/// The bytes received while transmitting (i.e., the echo of our own bytes) are discarded.
+/// Pending bytes received before a write are kept and returned by the next reads,
+/// up to the depth of the RX FIFO (8 bytes).
+///
+/// Each `write` call is a complete transmission, so `flush` does nothing.
+pub struct Rs485<UART, TX, RX, DE> {
+    pub(crate) serial: Serial<UART, TX, RX>,
+    pub(crate) de: DE,
+    pub(crate) stash: RxStash,
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, DE: OutputPin> Rs485<UART, TX, RX, DE> {
+    /// Wraps a [`Serial`] instance with a driver enable pin.
+    ///
+    /// The driver enable pin is released (i.e., set low) right away.
+    pub fn new(serial: Serial<UART, TX, RX>, mut de: DE) -> Self {
+        let _ = de.set_low();
+        Self {
+            serial,
+            de,
+            stash: RxStash::new(),
+        }
+    }
+
+    /// Releases the [`Serial`] instance and the driver enable pin
+    ///
+    /// The pending bytes kept from the RX FIFO during the last write are lost.
+    pub fn free(self) -> (Serial<UART, TX, RX>, DE) {
+        (self.serial, self.de)
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>> Serial<UART, TX, RX> {
+    /// Discards the bytes in the RX FIFO.
+    pub(crate) fn discard_rx(&mut self) {
+        while serial::Read::read(&mut self.rx).is_ok() {}
+    }
+
+    /// Moves the pending bytes of the RX FIFO to `stash` before transmitting,
+    /// so that discarding the echo does not drop them.
+    pub(crate) fn stash_rx(&mut self, stash: &mut RxStash) {
+        while stash.len < RX_FIFO_DEPTH {
+            match serial::Read::read(&mut self.rx) {
+                Ok(byte) => {
+                    stash.buf[stash.len] = byte;
+                    stash.len += 1;
+                }
+                Err(_) => break,
+            }
+        }
+    }
+
+    /// Waits for the last frame to leave the shift register.
+    ///
+    /// The TX FIFO must hold at most one byte, so the busy wait lasts up to two frames.
+    pub(crate) fn wait_last_frame(&mut self) {
+        // The watermark is pending as soon as the last byte moves to the shift register
+        self.tx.set_watermark(WatermarkValue::W1);
+        while !self.tx.is_interrupt_pending() {}
+        self.finish_frame(mcycle::read64());
+    }
+
+    /// Busy waits until the frame that started at `start` (in `mcycle` ticks) is over.
+    pub(crate) fn finish_frame(&self, start: u64) {
+        let frame_cycles = self.frame_cycles();
+        while mcycle::read64().wrapping_sub(start) < frame_cycles {}
+    }
+
+    /// Returns the number of whole `MTIME` ticks in a frame.
+    #[cfg(feature = "async")]
+    pub(crate) fn frame_ticks(&self, mtime_freq: usize) -> u64 {
+        // The frame lasts `div + 1` core clock cycles per bit
+        let bits = self.frame_cycles() / (self.uart.div().read().bits() as u64 + 1);
+        bits * mtime_freq as u64 / self.baud_rate().0 as u64
+    }
+}
+
+/// Depth of the RX FIFO
+const RX_FIFO_DEPTH: usize = 8;
+
+/// Pending bytes received before an [`Rs485`] write, kept apart from the echo of our own bytes.
+pub(crate) struct RxStash {
+    buf: [u8; RX_FIFO_DEPTH],
+    len: usize,
+}
+
+impl RxStash {
+    const fn new() -> Self {
+        Self {
+            buf: [0; RX_FIFO_DEPTH],
+            len: 0,
+        }
+    }
+
+    /// Returns true if there are no pending bytes.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Moves the pending bytes to `buf`, returning the number of bytes read.
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> usize {
+        let count = self.len.min(buf.len());
+        buf[..count].copy_from_slice(&self.buf[..count]);
+        self.buf.copy_within(count..self.len, 0);
+        self.len -= count;
+        count
+    }
+}
+
+/// Releases the driver enable pin of an [`Rs485`] transmission when dropped.
+///
+/// Writes hold a guard while the driver is enabled, so that cancelling an async write
+/// does not leave the transceiver driving the bus.
+pub(crate) struct DriverGuard<'a, DE: OutputPin> {
+    de: &'a mut DE,
+}
+
+impl<'a, DE: OutputPin> DriverGuard<'a, DE> {
+    /// Asserts the driver enable pin.
+    pub(crate) fn enable(de: &'a mut DE) -> Result<Self, embedded_io::ErrorKind> {
+        de.set_high().map_err(|_| embedded_io::ErrorKind::Other)?;
+        Ok(Self { de })
+    }
+
+    /// Releases the driver enable pin once the transmission is complete.
+    pub(crate) fn release(self) -> Result<(), embedded_io::ErrorKind> {
+        let mut this = core::mem::ManuallyDrop::new(self);
+        this.de.set_low().map_err(|_| embedded_io::ErrorKind::Other)
+    }
+}
+
+impl<DE: OutputPin> Drop for DriverGuard<'_, DE> {
+    fn drop(&mut self) {
+        let _ = self.de.set_low();
+    }
+}
+
+impl<UART, TX, RX, DE> embedded_io::ErrorType for Rs485<UART, TX, RX, DE> {
+    type Error = embedded_io::ErrorKind;
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, DE: OutputPin> embedded_io::Read
+    for Rs485<UART, TX, RX, DE>
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if !self.stash.is_empty() && !buf.is_empty() {
+            return Ok(self.stash.read(buf));
+        }
+        embedded_io::Read::read(&mut self.serial.rx, buf)
+    }
+}
+
+impl<UART: UartX, TX: TxPin<UART>, RX: RxPin<UART>, DE: OutputPin> embedded_io::Write
+    for Rs485<UART, TX, RX, DE>
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let Self { serial, de, stash } = self;
+        serial.stash_rx(stash);
+        let driver = DriverGuard::enable(de)?;
+        for byte in buf {
+            nb::block!(serial::Write::write(&mut serial.tx, *byte)).unwrap();
+            serial.discard_rx();
+        }
+        serial.wait_last_frame();
+        let res = driver.release();
+        // The echo of the last frame is received during its stop bit
+        serial.discard_rx();
+        res.map(|()| buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // The driver enable pin is only released once the transmission is complete
+        Ok(())
+    }
+}
@@ -0,0 +1,513 @@
+//! Bit-banged software serial interface
+//!
+//! [`SoftSerial`] drives any output pin as TX and any GPIO input pin as RX.
+//! Bits are timed with the `mcycle` counter, so it works at up to 115200 baud.
+//!
+//! # Transmission
+//!
+//! Each frame is sent inside a critical section to keep the bit timing accurate.
+//! Interrupts are therefore delayed for up to one frame (≈87 µs at 115200 baud).
+//!
+//! # Reception
+//!
+//! Start bits are detected with the falling edge interrupt of the RX pin.
+//! The whole frame is then sampled in the interrupt handler and stored in a
+//! caller-provided ring buffer through a `'static` [`SoftRxState`].
+//! The GPIO interrupt handler of the RX pin must call [`SoftRxState::on_interrupt`].
+//! When the `async` feature is enabled, the HAL already provides the GPIO interrupt
+//! handlers and forwards the interrupt to the software serial interface.
+//!
+//! # Example
+//!```ignore
+//! static SOFT_RX: SoftRxState = SoftRxState::new();
+//! static mut SOFT_RX_BUF: [u8; 16] = [0; 16];
+//!
+//! let tx = pins.pin10.into_output();
+//! let rx = pins.pin11.into_pull_up_input();
+//! let buf = unsafe { &mut *addr_of_mut!(SOFT_RX_BUF) };
+//! let mut serial = SoftSerial::new(tx, rx, 9_600.bps(), clocks, &SOFT_RX, buf);
+//! unsafe { serial.enable_exti(&plic) };
+//!
+//! #[riscv_rt::external_interrupt(ExternalInterrupt::GPIO11)]
+//! fn gpio11_handler() {
+//!     SOFT_RX.on_interrupt();
+//! }
+//!```
+
+use super::RingBuffer;
+use crate::{clock::Clocks, gpio::PinIndex, time::Bps};
+use e310x::{
+    interrupt::{ExternalInterrupt, Priority},
+    Gpio0, Plic,
+};
+use embedded_hal::digital::{InputPin, OutputPin};
+use embedded_hal_nb::serial;
+use portable_atomic::{AtomicU32, AtomicUsize, Ordering};
+use riscv::{interrupt::InterruptNumber, register::mcycle};
+
+/// Maximum supported baud rate
+pub const SOFT_SERIAL_MAX_BAUD_RATE: u32 = 115_200;
+
+/// Pin index value for a detached [`SoftRxState`]
+const NO_PIN: usize = usize::MAX;
+
+#[cfg(feature = "async")]
+const N_PINS: usize = 32;
+
+/// Active software serial receivers, indexed by pin
+#[cfg(feature = "async")]
+static SOFT_RX_STATES: [portable_atomic::AtomicPtr<SoftRxState>; N_PINS] =
+    [const { portable_atomic::AtomicPtr::new(core::ptr::null_mut()) }; N_PINS];
+
+/// Services the GPIO interrupt of a pin used as software serial receiver.
+///
+/// Returns false if the pin is not used by any software serial receiver.
+#[cfg(feature = "async")]
+pub(crate) fn on_soft_rx_irq(pin_index: usize) -> bool {
+    let state = SOFT_RX_STATES[pin_index].load(Ordering::Acquire);
+    // SAFETY: registered states are 'static
+    match unsafe { state.as_ref() } {
+        Some(state) => {
+            state.on_interrupt();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the number of `mcycle` ticks per bit for the given baud rate.
+fn bit_cycles(baud_rate: Bps, clocks: &Clocks) -> u32 {
+    assert!(baud_rate.0 > 0 && baud_rate.0 <= SOFT_SERIAL_MAX_BAUD_RATE);
+    clocks.coreclk().0 / baud_rate.0
+}
+
+/// Busy waits until `cycles` ticks of `mcycle` passed since `start`.
+#[inline(always)]
+fn wait_until(start: u64, cycles: u64) {
+    while mcycle::read64().wrapping_sub(start) < cycles {}
+}
+
+/// Returns the external interrupt source of a GPIO pin.
+fn pin_interrupt_source(pin_index: usize) -> ExternalInterrupt {
+    ExternalInterrupt::from_number(ExternalInterrupt::GPIO0.number() + pin_index).unwrap()
+}
+
+/// Shared state between a [`SoftRx`] instance and the GPIO interrupt handler of its pin.
+pub struct SoftRxState {
+    rx: RingBuffer,
+    pin_index: AtomicUsize,
+    bit_cycles: AtomicU32,
+    overruns: AtomicU32,
+    framing_errors: AtomicU32,
+}
+
+impl Default for SoftRxState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SoftRxState {
+    /// Creates a new, detached, software serial receiver state.
+    pub const fn new() -> Self {
+        Self {
+            rx: RingBuffer::new(),
+            pin_index: AtomicUsize::new(NO_PIN),
+            bit_cycles: AtomicU32::new(0),
+            overruns: AtomicU32::new(0),
+            framing_errors: AtomicU32::new(0),
+        }
+    }
+
+    /// Interrupt handler for the software serial receiver.
+    ///
+    /// This function must be called from the GPIO interrupt handler of the RX pin.
+    /// If a start bit is detected, it samples the whole frame before returning.
+    pub fn on_interrupt(&self) {
+        let pin_index = self.pin_index.load(Ordering::Acquire);
+        if pin_index == NO_PIN {
+            return;
+        }
+        let gpio = unsafe { Gpio0::steal() };
+        let pin_mask = 1 << pin_index;
+        if gpio.fall_ip().read().bits() & pin_mask == 0 {
+            return;
+        }
+
+        // The falling edge of the start bit happened right before entering the handler
+        let start = mcycle::read64();
+        let bit = self.bit_cycles.load(Ordering::Relaxed) as u64;
+        let is_high = || gpio.input_val().read().bits() & pin_mask != 0;
+
+        // Sample the middle of the start bit to filter glitches
+        wait_until(start, bit / 2);
+        if !is_high() {
+            // Sample the middle of each data bit, LSB first
+            let mut byte = 0u8;
+            for i in 0..8 {
+                wait_until(start, bit * (3 + 2 * i) / 2);
+                if is_high() {
+                    byte |= 1 << i;
+                }
+            }
+
+            // Sample the middle of the stop bit
+            wait_until(start, bit * 19 / 2);
+            if !is_high() {
+                self.framing_errors.fetch_add(1, Ordering::Relaxed);
+            } else if !self.rx.push(byte) {
+                self.overruns.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        // Clear the falling edges detected within the frame
+        unsafe { gpio.fall_ip().write(|w| w.bits(pin_mask)) };
+    }
+
+    /// Returns the number of received bytes dropped because the ring buffer was full.
+    pub fn overruns(&self) -> u32 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of frames dropped because the stop bit was not detected.
+    pub fn framing_errors(&self) -> u32 {
+        self.framing_errors.load(Ordering::Relaxed)
+    }
+}
+
+/// Software serial receiver half
+pub struct SoftRx<PIN> {
+    pin: PIN,
+    state: &'static SoftRxState,
+}
+
+impl<PIN: InputPin + PinIndex> SoftRx<PIN> {
+    /// Configures a GPIO input pin as software serial receiver.
+    ///
+    /// The falling edge interrupt of the pin is enabled right away, so incoming bytes
+    /// are stored in `buf` as soon as the pin external interrupt is enabled in the PLIC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buf` is empty or if the baud rate is higher than [`SOFT_SERIAL_MAX_BAUD_RATE`].
+    pub fn new(
+        pin: PIN,
+        baud_rate: Bps,
+        clocks: Clocks,
+        state: &'static SoftRxState,
+        buf: &'static mut [u8],
+    ) -> Self {
+        assert!(!buf.is_empty());
+        let bit_cycles = bit_cycles(baud_rate, &clocks);
+        let pin_mask = 1 << PIN::INDEX;
+
+        state.overruns.store(0, Ordering::Relaxed);
+        state.framing_errors.store(0, Ordering::Relaxed);
+        state.bit_cycles.store(bit_cycles, Ordering::Relaxed);
+        // SAFETY: the buffer is 'static and exclusively owned by the state until `free`
+        unsafe { state.rx.init(buf.as_mut_ptr(), buf.len()) };
+        state.pin_index.store(PIN::INDEX, Ordering::Release);
+        #[cfg(feature = "async")]
+        SOFT_RX_STATES[PIN::INDEX].store(
+            state as *const SoftRxState as *mut SoftRxState,
+            Ordering::Release,
+        );
+
+        // Listen for start bits
+        let gpio = unsafe { Gpio0::steal() };
+        unsafe {
+            gpio.fall_ip().write(|w| w.bits(pin_mask));
+            gpio.fall_ie().modify(|r, w| w.bits(r.bits() | pin_mask));
+        }
+
+        Self { pin, state }
+    }
+
+    /// Disables the falling edge interrupt and releases the pin and the ring buffer.
+    pub fn free(self) -> (PIN, &'static mut [u8]) {
+        let pin_mask = 1 << PIN::INDEX;
+        let gpio = unsafe { Gpio0::steal() };
+        unsafe { gpio.fall_ie().modify(|r, w| w.bits(r.bits() & !pin_mask)) };
+
+        #[cfg(feature = "async")]
+        SOFT_RX_STATES[PIN::INDEX].store(core::ptr::null_mut(), Ordering::Release);
+        self.state.pin_index.store(NO_PIN, Ordering::Release);
+        let (ptr, len) = self.state.rx.deinit();
+        // SAFETY: the pointer comes from the 'static buffer provided in `new`
+        let buf = unsafe { core::slice::from_raw_parts_mut(ptr, len) };
+        (self.pin, buf)
+    }
+
+    /// Enables the external interrupt source for the RX pin.
+    ///
+    /// # Note
+    /// This function enables the external interrupt source in the PLIC,
+    /// but does not enable the PLIC peripheral itself. To enable the plic peripheral
+    /// you must call [`Plic::enable()`](riscv-peripheral::plic::enables::ENABLES::enable).
+    ///
+    /// # Safety
+    /// Enabling an interrupt source can break mask-based critical sections.
+    pub unsafe fn enable_exti(&mut self, plic: &Plic) {
+        let ctx = plic.ctx0();
+        ctx.enables().enable(pin_interrupt_source(PIN::INDEX));
+    }
+
+    /// Disables the external interrupt source for the RX pin.
+    pub fn disable_exti(&mut self, plic: &Plic) {
+        let ctx = plic.ctx0();
+        ctx.enables().disable(pin_interrupt_source(PIN::INDEX));
+    }
+
+    /// Sets the external interrupt source priority.
+    ///
+    /// # Safety
+    ///
+    /// Changing the priority level can break priority-based critical sections.
+    pub unsafe fn set_exti_priority(&mut self, plic: &Plic, priority: Priority) {
+        let priorities = plic.priorities();
+        priorities.set_priority(pin_interrupt_source(PIN::INDEX), priority);
+    }
+
+    /// Returns the number of received bytes dropped because the ring buffer was full.
+    pub fn overruns(&self) -> u32 {
+        self.state.overruns()
+    }
+
+    /// Returns the number of frames dropped because the stop bit was not detected.
+    pub fn framing_errors(&self) -> u32 {
+        self.state.framing_errors()
+    }
+}
+
+impl<PIN: InputPin + PinIndex> serial::ErrorType for SoftRx<PIN> {
+    type Error = serial::ErrorKind;
+}
+
+impl<PIN: InputPin + PinIndex> embedded_io::ErrorType for SoftRx<PIN> {
+    type Error = embedded_io::ErrorKind;
+}
+
+impl<PIN: InputPin + PinIndex> serial::Read for SoftRx<PIN> {
+    #[inline]
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.state.rx.pop().ok_or(nb::Error::WouldBlock)
+    }
+}
+
+impl<PIN: InputPin + PinIndex> embedded_io::ReadReady for SoftRx<PIN> {
+    #[inline]
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.state.rx.is_empty())
+    }
+}
+
+impl<PIN: InputPin + PinIndex> embedded_io::Read for SoftRx<PIN> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while self.state.rx.is_empty() {} // first byte may block
+        Ok(self.state.rx.pop_slice(buf))
+    }
+}
+
+/// Software serial transmitter half
+pub struct SoftTx<PIN> {
+    pin: PIN,
+    bit_cycles: u32,
+}
+
+impl<PIN: OutputPin> SoftTx<PIN> {
+    /// Configures an output pin as software serial transmitter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the baud rate is higher than [`SOFT_SERIAL_MAX_BAUD_RATE`].
+    pub fn new(mut pin: PIN, baud_rate: Bps, clocks: Clocks) -> Self {
+        let bit_cycles = bit_cycles(baud_rate, &clocks);
+        // Idle line is high
+        let _ = pin.set_high();
+        Self { pin, bit_cycles }
+    }
+
+    /// Releases the pin
+    pub fn free(self) -> PIN {
+        self.pin
+    }
+
+    /// Sends a frame with one start bit, 8 data bits (LSB first), and one stop bit.
+    fn write_frame(&mut self, byte: u8) -> Result<(), PIN::Error> {
+        let bit = self.bit_cycles as u64;
+        let frame = (byte as u16) << 1 | 1 << 9;
+        riscv::interrupt::free(|| {
+            let start = mcycle::read64();
+            for i in 0..10 {
+                wait_until(start, bit * i as u64);
+                if frame & (1 << i) != 0 {
+                    self.pin.set_high()?;
+                } else {
+                    self.pin.set_low()?;
+                }
+            }
+            wait_until(start, bit * 10);
+            Ok(())
+        })
+    }
+}
+
+impl<PIN: OutputPin> serial::ErrorType for SoftTx<PIN> {
+    type Error = serial::ErrorKind;
+}
+
+impl<PIN: OutputPin> embedded_io::ErrorType for SoftTx<PIN> {
+    type Error = embedded_io::ErrorKind;
+}
+
+impl<PIN: OutputPin> serial::Write for SoftTx<PIN> {
+    #[inline]
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.write_frame(byte)
+            .map_err(|_| nb::Error::Other(serial::ErrorKind::Other))
+    }
+
+    #[inline]
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        // Frames are sent synchronously
+        Ok(())
+    }
+}
+
+impl<PIN: OutputPin> embedded_io::WriteReady for SoftTx<PIN> {
+    #[inline]
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+impl<PIN: OutputPin> embedded_io::Write for SoftTx<PIN> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for byte in buf {
+            self.write_frame(*byte)
+                .map_err(|_| embedded_io::ErrorKind::Other)?;
+        }
+        Ok(buf.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Software serial abstraction
+pub struct SoftSerial<TX, RX> {
+    tx: SoftTx<TX>,
+    rx: SoftRx<RX>,
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> SoftSerial<TX, RX> {
+    /// Configures a pair of pins to provide software serial communication.
+    ///
+    /// See [`SoftTx::new`] and [`SoftRx::new`] for more details.
+    pub fn new(
+        tx: TX,
+        rx: RX,
+        baud_rate: Bps,
+        clocks: Clocks,
+        state: &'static SoftRxState,
+        buf: &'static mut [u8],
+    ) -> Self {
+        Self {
+            tx: SoftTx::new(tx, baud_rate, clocks),
+            rx: SoftRx::new(rx, baud_rate, clocks, state, buf),
+        }
+    }
+
+    /// Enables the external interrupt source for the RX pin.
+    ///
+    /// # Safety
+    /// Enabling an interrupt source can break mask-based critical sections.
+    pub unsafe fn enable_exti(&mut self, plic: &Plic) {
+        self.rx.enable_exti(plic);
+    }
+
+    /// Disables the external interrupt source for the RX pin.
+    pub fn disable_exti(&mut self, plic: &Plic) {
+        self.rx.disable_exti(plic);
+    }
+
+    /// Sets the external interrupt source priority of the RX pin.
+    ///
+    /// # Safety
+    ///
+    /// Changing the priority level can break priority-based critical sections.
+    pub unsafe fn set_exti_priority(&mut self, plic: &Plic, priority: Priority) {
+        self.rx.set_exti_priority(plic, priority);
+    }
+
+    /// Splits the [`SoftSerial`] abstraction into a transmitter and a receiver half
+    pub fn split(self) -> (SoftTx<TX>, SoftRx<RX>) {
+        (self.tx, self.rx)
+    }
+
+    /// Releases the pins and the ring buffer
+    pub fn free(self) -> ((TX, RX), &'static mut [u8]) {
+        let tx = self.tx.free();
+        let (rx, buf) = self.rx.free();
+        ((tx, rx), buf)
+    }
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> serial::ErrorType for SoftSerial<TX, RX> {
+    type Error = serial::ErrorKind;
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> serial::Read for SoftSerial<TX, RX> {
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.rx.read()
+    }
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> serial::Write for SoftSerial<TX, RX> {
+    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.tx.write(byte)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.tx.flush()
+    }
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> embedded_io::ErrorType for SoftSerial<TX, RX> {
+    type Error = embedded_io::ErrorKind;
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> embedded_io::ReadReady for SoftSerial<TX, RX> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.rx.read_ready()
+    }
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> embedded_io::Read for SoftSerial<TX, RX> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.rx.read(buf)
+    }
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> embedded_io::WriteReady for SoftSerial<TX, RX> {
+    fn write_ready(&mut self) -> Result<bool, Self::Error> {
+        self.tx.write_ready()
+    }
+}
+
+impl<TX: OutputPin, RX: InputPin + PinIndex> embedded_io::Write for SoftSerial<TX, RX> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.tx.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.tx.flush()
+    }
+}
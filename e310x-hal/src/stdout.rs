@@ -15,3 +15,21 @@ impl<T: embedded_io::Write> Write for Stdout<'_, T> {
         Ok(())
     }
 }
+
+#[cfg(feature = "ufmt")]
+impl<T: embedded_io::Write> ufmt_write::uWrite for Stdout<'_, T> {
+    type Error = T::Error;
+
+    fn write_str(&mut self, s: &str) -> core::result::Result<(), Self::Error> {
+        for line in s.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    self.0.write_all(line.as_bytes())?;
+                    self.0.write_all(b"\r\n")?;
+                }
+                None => self.0.write_all(line.as_bytes())?,
+            }
+        }
+        Ok(())
+    }
+}
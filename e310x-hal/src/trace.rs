@@ -0,0 +1,264 @@
+//! Event trace recorder
+//!
+//! With the `trace` feature, tasks and interrupt handlers can record events in a RAM ring buffer.
+//! Each [`Event`] holds the `mcycle` counter, an event ID, and a user argument. Recording is
+//! lock-free: the slots are reserved with `amoadd` instructions through `portable-atomic`,
+//! so events can be recorded from any context without disabling interrupts.
+//!
+//! The PLIC dispatcher of the `e310x` crate records [`IRQ_ENTER`] and [`IRQ_EXIT`] events,
+//! with the interrupt number as argument, around each external interrupt handler.
+//!
+//! When the ring buffer is full, the oldest events are overwritten. The buffer can be dumped
+//! in a compact binary form ([`dump_binary`]) or as Chrome trace JSON ([`dump_chrome`]),
+//! which can be opened in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+//! Recording is paused while dumping. However, a [`record`] call that reserved its slot and was
+//! then preempted by the dumping context only writes the slot once the dump is over, so that
+//! event may be dumped stale or partially written. Dumping from thread mode (or from any context
+//! that never preempts a recorder) avoids it, as interrupt handlers complete their events
+//! before returning.
+//!
+//! # Example
+//!```ignore
+//! static mut EVENTS: [Event; 256] = [Event::EMPTY; 256];
+//!
+//! e310x_hal::trace::init(unsafe { &mut *addr_of_mut!(EVENTS) });
+//! e310x_hal::trace::record(SENSOR_READ, value);
+//! ...
+//! e310x_hal::trace::dump_chrome(&mut tx, clocks.coreclk())?;
+//!```
+
+use crate::time::Hertz;
+use core::fmt;
+use e310x::interrupt::ExternalInterrupt;
+use embedded_io::Write;
+use portable_atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use riscv::{register::mcycle, InterruptNumber};
+
+/// Event ID recorded when entering an external interrupt handler
+pub const IRQ_ENTER: u32 = 0xFFFF_FF00;
+
+/// Event ID recorded when leaving an external interrupt handler
+pub const IRQ_EXIT: u32 = 0xFFFF_FF01;
+
+/// Magic number at the beginning of the binary dump
+pub const BINARY_MAGIC: [u8; 4] = *b"E3TR";
+
+/// Traced event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct Event {
+    /// Lower 32 bits of the `mcycle` counter
+    pub cycle: u32,
+    /// Event ID
+    pub id: u32,
+    /// User argument
+    pub arg: u32,
+}
+
+impl Event {
+    /// Empty event, to initialize the ring buffer
+    pub const EMPTY: Self = Self {
+        cycle: 0,
+        id: 0,
+        arg: 0,
+    };
+}
+
+static BUFFER: AtomicPtr<Event> = AtomicPtr::new(core::ptr::null_mut());
+
+static CAPACITY: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of events recorded since the last reset of the buffer
+static HEAD: AtomicUsize = AtomicUsize::new(0);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Sets the ring buffer of the recorder and starts recording.
+///
+/// Any previous buffer and its events are discarded.
+pub fn init(buffer: &'static mut [Event]) {
+    ENABLED.store(false, Ordering::SeqCst);
+    CAPACITY.store(0, Ordering::SeqCst);
+    HEAD.store(0, Ordering::SeqCst);
+    BUFFER.store(buffer.as_mut_ptr(), Ordering::SeqCst);
+    CAPACITY.store(buffer.len(), Ordering::SeqCst);
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Pauses or resumes recording.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// Discards the recorded events.
+pub fn clear() {
+    HEAD.store(0, Ordering::SeqCst);
+}
+
+/// Records an event with the current `mcycle` counter.
+#[inline]
+pub fn record(id: u32, arg: u32) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let capacity = CAPACITY.load(Ordering::Relaxed);
+    if capacity == 0 {
+        return;
+    }
+    let cycle = mcycle::read() as u32;
+    let index = HEAD.fetch_add(1, Ordering::Relaxed) % capacity;
+    // SAFETY: the index is within the buffer, and each slot is written by one recorder at a time
+    unsafe {
+        BUFFER
+            .load(Ordering::Relaxed)
+            .add(index)
+            .write_volatile(Event { cycle, id, arg })
+    };
+}
+
+/// Returns the number of events lost because the ring buffer was full.
+pub fn overwritten() -> usize {
+    HEAD.load(Ordering::SeqCst)
+        .saturating_sub(CAPACITY.load(Ordering::SeqCst))
+}
+
+/// Snapshot of the ring buffer, taken with recording paused until it is dropped.
+///
+/// Pausing only stops new recordings: slots reserved by preempted [`record`] calls are read
+/// as they are (see the module documentation).
+struct Snapshot {
+    enabled: bool,
+    capacity: usize,
+    head: usize,
+    count: usize,
+    buffer: *const Event,
+}
+
+impl Snapshot {
+    /// Pauses recording and takes the state of the ring buffer.
+    fn take() -> Self {
+        let enabled = ENABLED.swap(false, Ordering::SeqCst);
+        let capacity = CAPACITY.load(Ordering::SeqCst);
+        let head = HEAD.load(Ordering::SeqCst);
+        Self {
+            enabled,
+            capacity,
+            head,
+            count: head.min(capacity),
+            buffer: BUFFER.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Returns the recorded events, oldest first.
+    fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        (self.head - self.count..self.head).map(|i| {
+            // SAFETY: the index is within the buffer. Recording is paused, although a preempted
+            // recorder may still write its slot later
+            unsafe { self.buffer.add(i % self.capacity).read_volatile() }
+        })
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        ENABLED.store(self.enabled, Ordering::SeqCst);
+    }
+}
+
+/// Writes the recorded events in binary form.
+///
+/// The dump starts with [`BINARY_MAGIC`], followed by the number of events as a little-endian
+/// `u32`, and the events, oldest first, as three little-endian `u32` (`cycle`, `id`, `arg`).
+pub fn dump_binary<W: Write>(w: &mut W) -> Result<(), W::Error> {
+    let snapshot = Snapshot::take();
+    w.write_all(&BINARY_MAGIC)?;
+    w.write_all(&(snapshot.count as u32).to_le_bytes())?;
+    for event in snapshot.events() {
+        w.write_all(&event.cycle.to_le_bytes())?;
+        w.write_all(&event.id.to_le_bytes())?;
+        w.write_all(&event.arg.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Adapter to write formatted text to an [`embedded_io::Write`], keeping the I/O error
+struct FmtWriter<'a, W: Write> {
+    w: &'a mut W,
+    error: Option<W::Error>,
+}
+
+impl<W: Write> fmt::Write for FmtWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.w.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+impl<W: Write> FmtWriter<'_, W> {
+    fn write(&mut self, args: fmt::Arguments) -> Result<(), W::Error> {
+        match fmt::Write::write_fmt(self, args) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(self.error.take().expect("formatting error")),
+        }
+    }
+}
+
+/// Writes the recorded events as Chrome trace JSON.
+///
+/// `coreclk` is the frequency of the `mcycle` counter, used to convert the timestamps to
+/// microseconds. Interrupt handlers are shown as duration events on a thread named `irq`,
+/// and the rest of the events as instant events on a thread named `events`.
+///
+/// The `mcycle` counter wraps around every 2^32 cycles, so there must be at least one event
+/// per wrap around for the timestamps to be monotonic.
+pub fn dump_chrome<W: Write>(w: &mut W, coreclk: Hertz) -> Result<(), W::Error> {
+    let mut w = FmtWriter { w, error: None };
+    let freq = u64::from(coreclk.0.max(1));
+    w.write(format_args!(
+        "{{\"traceEvents\":[\
+         {{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":0,\"args\":{{\"name\":\"events\"}}}},\
+         {{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":1,\"args\":{{\"name\":\"irq\"}}}}"
+    ))?;
+
+    let snapshot = Snapshot::take();
+    let mut last = None;
+    let mut cycles = 0u64;
+    for event in snapshot.events() {
+        // Extend the 32-bit cycle counter, assuming less than one wrap around between events
+        cycles += u64::from(event.cycle.wrapping_sub(last.unwrap_or(event.cycle)));
+        last = Some(event.cycle);
+        let micros = cycles * 1_000_000 / freq;
+        let nanos = cycles * 1_000_000_000 / freq % 1_000;
+        match event.id {
+            IRQ_ENTER | IRQ_EXIT => {
+                let ph = if event.id == IRQ_ENTER { 'B' } else { 'E' };
+                w.write(format_args!(",{{\"name\":\""))?;
+                match ExternalInterrupt::from_number(event.arg as usize) {
+                    Ok(irq) => w.write(format_args!("{irq:?}"))?,
+                    Err(_) => w.write(format_args!("IRQ {}", event.arg))?,
+                }
+                w.write(format_args!(
+                    "\",\"ph\":\"{ph}\",\"ts\":{micros}.{nanos:03},\"pid\":0,\"tid\":1}}"
+                ))?
+            }
+            id => w.write(format_args!(
+                ",{{\"name\":\"{id:#x}\",\"ph\":\"i\",\"s\":\"t\",\"ts\":{micros}.{nanos:03},\
+                 \"pid\":0,\"tid\":0,\"args\":{{\"arg\":{}}}}}",
+                event.arg
+            ))?,
+        }
+    }
+    w.write(format_args!("]}}\n"))
+}
+
+#[no_mangle]
+fn _plic_enter_hook(number: usize) {
+    record(IRQ_ENTER, number as u32);
+}
+
+#[no_mangle]
+fn _plic_exit_hook(number: usize) {
+    record(IRQ_EXIT, number as u32);
+}
@@ -99,6 +99,7 @@ impl Wdog {
     }
 
     pub fn set_cmp(&mut self, value: u16) {
+        self.unlock();
         unsafe { WDOG::steal().wdogcmp().write(|w| w.value().bits(value)) };
     }
 }
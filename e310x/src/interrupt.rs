@@ -172,7 +172,27 @@ unsafe fn plic_handler() {
     let plic = unsafe { crate::Plic::steal() };
     let claim = plic.ctx(Hart::H0).claim();
     if let Some(s) = claim.claim::<ExternalInterrupt>() {
+        #[cfg(feature = "plic-hooks")]
+        unsafe {
+            _plic_enter_hook(s.number())
+        }
         unsafe { _dispatch_external_interrupt(s.number()) }
+        #[cfg(feature = "plic-hooks")]
+        unsafe {
+            _plic_exit_hook(s.number())
+        }
         claim.complete(s);
     }
 }
+
+#[cfg(all(feature = "rt", feature = "plic-hooks"))]
+extern "Rust" {
+    #[doc = r" Called by the PLIC dispatcher before the handler of an external interrupt."]
+    #[doc = r""]
+    #[doc = r" It must be defined by another crate when the `plic-hooks` feature is enabled."]
+    fn _plic_enter_hook(number: usize);
+    #[doc = r" Called by the PLIC dispatcher after the handler of an external interrupt."]
+    #[doc = r""]
+    #[doc = r" It must be defined by another crate when the `plic-hooks` feature is enabled."]
+    fn _plic_exit_hook(number: usize);
+}
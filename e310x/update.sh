@@ -12,6 +12,9 @@ form -i lib.rs -o src/
 rm lib.rs
 cargo fmt
 
+# add the PLIC dispatcher hooks, which svd2rust does not generate
+patch -p1 < patches/plic-hooks.patch
+
 # combine generated device.x with memory.x
 mv device.x ints.x
 cat memory.x ints.x > device.x
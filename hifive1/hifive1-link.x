@@ -1,2 +1,11 @@
 INCLUDE hifive1-memory.x
 INCLUDE link.x
+
+/* RTT control block at the beginning of RAM, so debug probes find it at a fixed address */
+SECTIONS
+{
+  .rtt (NOLOAD) : ALIGN(4)
+  {
+    KEEP(*(.rtt .rtt.*));
+  } > REGION_DATA
+} INSERT BEFORE .data;
@@ -0,0 +1,90 @@
+//! [`defmt`] global logger on the serial stdout
+//!
+//! The defmt frames are written to the UART configured with [`configure`](crate::stdout::configure),
+//! so the same pins and baud rate are used for `sprintln!` and defmt. Do not mix both in the same
+//! application, as the host decoder cannot tell text from defmt frames.
+//!
+//! Timestamps are taken from the CLINT `mtime` counter, with microsecond resolution.
+//!
+//! With the `defmt-panic` feature, this module also provides a panic handler that logs the
+//! panic message, flushes the UART, and halts the core.
+//!
+//! The final binary must be linked with the `defmt.x` linker script (e.g., `-C link-arg=-Tdefmt.x`).
+//!
+//! # Example
+//!```ignore
+//! hifive1::stdout::configure(p.UART0, pin!(pins, uart0_tx), pin!(pins, uart0_rx), 115_200.bps(), clocks);
+//! defmt::info!("Hello from HiFive1");
+//!```
+
+use crate::stdout;
+use core::{
+    ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use e310x_hal::e310x::Clint;
+
+/// Frequency of the `mtime` counter
+const MTIME_FREQ: u64 = 32_768;
+
+defmt::timestamp!("{=u64:us}", {
+    let ticks = unsafe { Clint::steal() }.mtimer().mtime().read();
+    ticks / MTIME_FREQ * 1_000_000 + (ticks % MTIME_FREQ) * 1_000_000 / MTIME_FREQ
+});
+
+#[defmt::global_logger]
+struct Logger;
+
+/// Whether the logger is acquired
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Critical section state to restore when the logger is released
+static mut CS_RESTORE: critical_section::RestoreState = critical_section::RestoreState::invalid();
+
+static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {
+        let restore = unsafe { critical_section::acquire() };
+        // Nested logging (e.g., from a `Format` implementation) would corrupt the current frame
+        if TAKEN.load(Ordering::Relaxed) {
+            panic!("defmt logger taken reentrantly")
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+
+        // SAFETY: accessed within the critical section only
+        unsafe {
+            CS_RESTORE = restore;
+            (*ptr::addr_of_mut!(ENCODER)).start_frame(stdout::write_bytes);
+        }
+    }
+
+    unsafe fn flush() {
+        stdout::flush();
+    }
+
+    unsafe fn release() {
+        (*ptr::addr_of_mut!(ENCODER)).end_frame(stdout::write_bytes);
+        TAKEN.store(false, Ordering::Relaxed);
+        let restore = CS_RESTORE;
+        critical_section::release(restore);
+    }
+
+    unsafe fn write(bytes: &[u8]) {
+        (*ptr::addr_of_mut!(ENCODER)).write(bytes, stdout::write_bytes);
+    }
+}
+
+#[cfg(feature = "defmt-panic")]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    // A panic while the logger is taken cannot be logged without corrupting the current frame
+    if !TAKEN.load(Ordering::Relaxed) {
+        defmt::error!("{}", defmt::Display2Format(info));
+    }
+    stdout::flush();
+    riscv::interrupt::disable();
+    loop {
+        riscv::asm::wfi();
+    }
+}
@@ -4,6 +4,9 @@
 #![no_std]
 
 pub use e310x_hal as hal;
+#[cfg(feature = "ufmt")]
+#[doc(hidden)]
+pub use ufmt;
 
 pub mod clock;
 pub use clock::configure as configure_clocks;
@@ -24,6 +27,24 @@ pub use led::{Led, BLUE};
 pub mod stdout;
 pub use stdout::configure as configure_stdout;
 
+#[cfg(feature = "log")]
+pub mod logger;
+
+#[cfg(feature = "defmt")]
+pub mod defmt_logger;
+
+#[cfg(feature = "rtt")]
+pub mod rtt;
+
+#[cfg(feature = "shell")]
+pub mod shell;
+
+#[cfg(all(feature = "panic", feature = "defmt-panic"))]
+compile_error!("features `panic` and `defmt-panic` cannot be enabled at the same time");
+
+#[cfg(feature = "panic")]
+pub mod panic;
+
 #[doc(hidden)]
 #[cfg(any(
     feature = "board-hifive1",
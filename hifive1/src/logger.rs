@@ -0,0 +1,187 @@
+//! [`log`] backend on top of the serial stdout
+//!
+//! Each record is printed as a single line, prefixed by the `mtime` timestamp
+//! (in seconds since reset) and the level of the record:
+//!
+//! ```text
+//! [    12.000335] INFO  app::sensor: temperature: 21 C
+//! ```
+//!
+//! # Filtering
+//!
+//! - At compile time, records are filtered by the `max_level_*` and `release_max_level_*`
+//!   features of the [`log`] crate.
+//! - At runtime, records are filtered by a default level ([`set_max_level`]) and by up to
+//!   [`MAX_MODULE_FILTERS`] per-module levels ([`set_module_level`]). Module filters apply to
+//!   the module and its submodules, and the most specific filter wins.
+//!
+//! # Example
+//!```ignore
+//! hifive1::stdout::configure(p.UART0, pin!(pins, uart0_tx), pin!(pins, uart0_rx), 115_200.bps(), clocks);
+//! hifive1::logger::init(LevelFilter::Info).unwrap();
+//! hifive1::logger::set_module_level("app::sensor", LevelFilter::Trace).unwrap();
+//!
+//! log::info!("Hello from HiFive1");
+//!```
+
+use crate::stdout;
+use core::cell::RefCell;
+use critical_section::Mutex;
+use e310x_hal::e310x::Clint;
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+/// Maximum number of per-module filters
+pub const MAX_MODULE_FILTERS: usize = 8;
+
+/// Frequency of the `mtime` counter
+const MTIME_FREQ: u64 = 32_768;
+
+/// Error returned when all the per-module filters are in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleFiltersFull;
+
+/// Runtime filtering configuration
+struct Filters {
+    default: LevelFilter,
+    modules: [Option<(&'static str, LevelFilter)>; MAX_MODULE_FILTERS],
+}
+
+impl Filters {
+    /// Returns the level filter that applies to a given target.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .flatten()
+            .filter(|(module, _)| matches_module(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// Returns the most verbose level filter of the configuration.
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .flatten()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+
+    /// Updates the global maximum level of the [`log`] crate.
+    fn apply(&self) {
+        // SAFETY: only called within a critical section
+        unsafe { log::set_max_level_racy(self.max_level()) };
+    }
+}
+
+static FILTERS: Mutex<RefCell<Filters>> = Mutex::new(RefCell::new(Filters {
+    default: LevelFilter::Off,
+    modules: [None; MAX_MODULE_FILTERS],
+}));
+
+/// Returns true if `target` is `module` or one of its submodules.
+fn matches_module(target: &str, module: &str) -> bool {
+    match target.strip_prefix(module) {
+        Some(rest) => rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+/// Logger that prints the records to the serial stdout
+struct Logger;
+
+static LOGGER: Logger = Logger;
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        critical_section::with(|cs| {
+            metadata.level() <= FILTERS.borrow_ref(cs).level(metadata.target())
+        })
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let ticks = unsafe { Clint::steal() }.mtimer().mtime().read();
+        let secs = ticks / MTIME_FREQ;
+        let micros = (ticks % MTIME_FREQ) * 1_000_000 / MTIME_FREQ;
+        stdout::write_fmt(format_args!(
+            "[{:>6}.{:06}] {:<5} {}: {}\n",
+            secs,
+            micros,
+            record.level(),
+            record.target(),
+            record.args()
+        ));
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the logger with a default level filter.
+///
+/// Stdout must be configured with [`configure`](crate::stdout::configure) for the records to be printed.
+///
+/// # Errors
+///
+/// Returns an error if a logger is already installed.
+pub fn init(level: LevelFilter) -> Result<(), SetLoggerError> {
+    critical_section::with(|cs| {
+        // SAFETY: no other logger function can run within the critical section
+        unsafe { log::set_logger_racy(&LOGGER) }?;
+        let mut filters = FILTERS.borrow_ref_mut(cs);
+        filters.default = level;
+        filters.apply();
+        Ok(())
+    })
+}
+
+/// Sets the default level filter, used by the modules without a specific filter.
+pub fn set_max_level(level: LevelFilter) {
+    critical_section::with(|cs| {
+        let mut filters = FILTERS.borrow_ref_mut(cs);
+        filters.default = level;
+        filters.apply();
+    });
+}
+
+/// Sets the level filter of a module and its submodules (e.g., `"app::sensor"`).
+///
+/// If the module already has a filter, it is replaced.
+///
+/// # Errors
+///
+/// Returns an error if the module has no filter and all the filters are in use.
+pub fn set_module_level(module: &'static str, level: LevelFilter) -> Result<(), ModuleFiltersFull> {
+    critical_section::with(|cs| {
+        let mut filters = FILTERS.borrow_ref_mut(cs);
+        let slot = match filters
+            .modules
+            .iter()
+            .position(|f| matches!(f, Some((m, _)) if *m == module))
+        {
+            Some(i) => i,
+            None => filters
+                .modules
+                .iter()
+                .position(Option::is_none)
+                .ok_or(ModuleFiltersFull)?,
+        };
+        filters.modules[slot] = Some((module, level));
+        filters.apply();
+        Ok(())
+    })
+}
+
+/// Removes the level filter of a module, if any.
+pub fn clear_module_level(module: &str) {
+    critical_section::with(|cs| {
+        let mut filters = FILTERS.borrow_ref_mut(cs);
+        for filter in filters.modules.iter_mut() {
+            if matches!(filter, Some((m, _)) if *m == module) {
+                *filter = None;
+            }
+        }
+        filters.apply();
+    });
+}
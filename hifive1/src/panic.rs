@@ -0,0 +1,239 @@
+//! Panic handler with crash record
+//!
+//! When the application panics, the handler:
+//!
+//! 1. Stores a [`CrashRecord`] in the AON backup registers 0 to 4, which keep their
+//!    value through a watchdog reset and while the core sleeps.
+//! 2. Prints the panic message and location to stdout and flushes it.
+//! 3. Blinks an SOS pattern (`... --- ...`) on the error LED registered with `set_led`
+//!    (the red LED on HiFive1 boards, and the blue LED on RedV boards).
+//! 4. Halts the core or resets it through the watchdog, as selected with [`set_action`].
+//!
+//! After the reboot, the record can be retrieved with [`crash_record`] and cleared with
+//! [`clear_crash_record`]. The panicking file is stored as a hash to fit in the backup
+//! registers. Use [`file_hash`] to match it against the source files of the application.
+//!
+//! This feature cannot be enabled with `defmt-panic`, as both provide a panic handler.
+//!
+//! # Example
+//!```ignore
+//! if let Some(record) = hifive1::panic::crash_record() {
+//!     sprintln!("previous run panicked at line {} (mepc = {:#x})", record.line, record.mepc);
+//!     hifive1::panic::clear_crash_record();
+//! }
+//! hifive1::panic::set_led(red);
+//! hifive1::panic::set_action(PanicAction::Reset);
+//!```
+
+#[cfg(any(
+    feature = "board-hifive1",
+    feature = "board-hifive1-revb",
+    feature = "board-redv"
+))]
+use crate::led::Led;
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+use e310x_hal::{
+    e310x::{Pmu, Wdog},
+    pmu::PMUExt,
+    wdog::WdogExt,
+};
+
+/// Value of [`CrashRecord::magic`] for a valid record
+const MAGIC: u32 = 0xDEAD_C0DE;
+
+/// LED used to report the panic
+#[cfg(any(feature = "board-hifive1", feature = "board-hifive1-revb"))]
+pub type ErrorLed = crate::led::RED;
+
+/// LED used to report the panic
+#[cfg(feature = "board-redv")]
+pub type ErrorLed = crate::led::BLUE;
+
+/// Action taken after reporting the panic
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum PanicAction {
+    /// Halt the core with interrupts disabled (default)
+    Halt = 0,
+    /// Reset the core through the watchdog
+    Reset = 1,
+}
+
+/// Crash record stored in the AON backup registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub struct CrashRecord {
+    magic: u32,
+    /// FNV-1a hash of the file where the panic occurred (see [`file_hash`])
+    pub file_hash: u32,
+    /// Line where the panic occurred
+    pub line: u32,
+    /// Value of `mepc` when the panic occurred (i.e., PC of the last trap)
+    pub mepc: u32,
+    /// Value of `mcause` when the panic occurred (i.e., cause of the last trap)
+    pub mcause: u32,
+}
+
+impl CrashRecord {
+    const fn empty() -> Self {
+        Self {
+            magic: 0,
+            file_hash: 0,
+            line: 0,
+            mepc: 0,
+            mcause: 0,
+        }
+    }
+}
+
+/// Action taken after reporting the panic
+static ACTION: AtomicU8 = AtomicU8::new(PanicAction::Halt as u8);
+
+/// Whether the panic handler is running
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+#[cfg(any(
+    feature = "board-hifive1",
+    feature = "board-hifive1-revb",
+    feature = "board-redv"
+))]
+static mut LED: Option<ErrorLed> = None;
+
+/// Returns the FNV-1a hash of a file path, as stored in [`CrashRecord::file_hash`].
+pub const fn file_hash(file: &str) -> u32 {
+    let bytes = file.as_bytes();
+    let mut hash = 0x811C_9DC5_u32;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+    hash
+}
+
+/// Sets the action taken after reporting the panic.
+pub fn set_action(action: PanicAction) {
+    ACTION.store(action as u8, Ordering::Relaxed);
+}
+
+/// Registers the LED that blinks the SOS pattern on panic.
+#[cfg(any(
+    feature = "board-hifive1",
+    feature = "board-hifive1-revb",
+    feature = "board-redv"
+))]
+pub fn set_led(led: ErrorLed) {
+    critical_section::with(|_| {
+        unsafe { &mut *core::ptr::addr_of_mut!(LED) }.replace(led);
+    });
+}
+
+/// Returns the crash record of the last panic, if any.
+pub fn crash_record() -> Option<CrashRecord> {
+    let mut record = CrashRecord::empty();
+    // SAFETY: the record is made of `u32` fields and fits in the backup registers
+    let restored = unsafe { Pmu::steal().restore_backup(&mut record) };
+    (restored.is_ok() && record.magic == MAGIC).then_some(record)
+}
+
+/// Clears the crash record, leaving the rest of the backup registers untouched.
+pub fn clear_crash_record() {
+    // SAFETY: the record is made of `u32` fields and fits in the backup registers
+    let _ = unsafe { Pmu::steal().store_backup(&CrashRecord::empty()) };
+}
+
+/// Busy-waits for a number of milliseconds on the `mtime` counter.
+#[cfg(any(
+    feature = "board-hifive1",
+    feature = "board-hifive1-revb",
+    feature = "board-redv"
+))]
+fn delay_ms(ms: u64) {
+    const MTIME_FREQ: u64 = 32_768;
+    let mtime = unsafe { e310x_hal::e310x::Clint::steal() }.mtimer().mtime();
+    let end = mtime.read() + ms * MTIME_FREQ / 1_000;
+    while mtime.read() < end {}
+}
+
+/// Blinks the SOS pattern three times on the error LED, if registered.
+#[cfg(any(
+    feature = "board-hifive1",
+    feature = "board-hifive1-revb",
+    feature = "board-redv"
+))]
+fn blink() {
+    // SAFETY: interrupts are disabled, and the handler does not run reentrantly
+    let Some(led) = (unsafe { &mut *core::ptr::addr_of_mut!(LED) }) else {
+        return;
+    };
+    for _ in 0..3 {
+        for on_ms in [150, 150, 150, 450, 450, 450, 150, 150, 150] {
+            led.on();
+            delay_ms(on_ms);
+            led.off();
+            delay_ms(150);
+        }
+        delay_ms(1_000);
+    }
+}
+
+#[cfg(not(any(
+    feature = "board-hifive1",
+    feature = "board-hifive1-revb",
+    feature = "board-redv"
+)))]
+fn blink() {}
+
+/// Resets the core by letting the watchdog expire immediately.
+fn reset() -> ! {
+    let mut wdog = unsafe { Wdog::steal() }.configure().freeze();
+    wdog.set_cmp(0);
+    wdog.feed();
+    unsafe { Wdog::steal() }
+        .configure()
+        .enable()
+        .enable_reset()
+        .freeze();
+    loop {
+        riscv::asm::wfi();
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    riscv::interrupt::disable();
+
+    // A panic while reporting a panic goes straight to the final action
+    if !PANICKING.load(Ordering::Relaxed) {
+        PANICKING.store(true, Ordering::Relaxed);
+
+        let (file_hash, line) = info
+            .location()
+            .map_or((0, 0), |l| (file_hash(l.file()), l.line()));
+        let record = CrashRecord {
+            magic: MAGIC,
+            file_hash,
+            line,
+            mepc: riscv::register::mepc::read() as u32,
+            mcause: riscv::register::mcause::read().bits() as u32,
+        };
+        // SAFETY: the record is made of `u32` fields and fits in the backup registers
+        let _ = unsafe { Pmu::steal().store_backup(&record) };
+
+        crate::sprintln!("\n{}", info);
+        crate::stdout::flush();
+
+        blink();
+    }
+
+    match ACTION.load(Ordering::Relaxed) {
+        1 => reset(),
+        _ => loop {
+            riscv::asm::wfi();
+        },
+    }
+}
@@ -0,0 +1,294 @@
+//! SEGGER Real-Time Transfer (RTT) transport
+//!
+//! RTT exchanges data with the host through ring buffers in RAM that the debug probe
+//! reads and writes while the core is running, so no pins are needed.
+//!
+//! The control block lives in the `.rtt` section, which `hifive1-link.x` places at the
+//! beginning of RAM (`0x8000_0000`). Probes scan the RAM for the `SEGGER RTT` ID, but
+//! OpenOCD can be pointed at it directly:
+//!
+//! ```text
+//! rtt setup 0x80000000 72 "SEGGER RTT"
+//! rtt start
+//! rtt server start 9090 0
+//! ```
+//!
+//! One up channel (target to host) and one down channel (host to target) are provided,
+//! both named `Terminal`. [`UpChannel`] implements [`embedded_io::Write`], and can be used
+//! as stdout sink with [`configure_rtt`](crate::stdout::configure_rtt). [`DownChannel`]
+//! implements [`embedded_io::Read`], e.g., to receive commands from the host.
+//!
+//! # Example
+//!```ignore
+//! static mut UP: [u8; 1024] = [0; 1024];
+//! static mut DOWN: [u8; 64] = [0; 64];
+//!
+//! let (up, down) = unsafe { (&mut *addr_of_mut!(UP), &mut *addr_of_mut!(DOWN)) };
+//! let (up, mut down) = hifive1::rtt::init(up, down).unwrap();
+//! hifive1::stdout::configure_rtt(up);
+//! sprintln!("Hello from RTT");
+//!```
+
+use core::{
+    cell::UnsafeCell,
+    ptr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use e310x_hal::prelude::embedded_io::{self, ErrorKind};
+
+/// Control block ID searched by the debug probes
+const ID: &[u8; 16] = b"SEGGER RTT\0\0\0\0\0\0";
+
+/// Name of the channels
+const CHANNEL_NAME: &[u8] = b"Terminal\0";
+
+/// Behavior of the up channel when its buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum ChannelMode {
+    /// Discard the data that does not fit entirely
+    NoBlockSkip = 0,
+    /// Write the data that fits and discard the rest
+    NoBlockTrim = 1,
+    /// Wait for the host to read the buffer
+    ///
+    /// *Warning:* the target hangs if no debug probe reads the channel.
+    BlockIfFull = 2,
+}
+
+/// RTT channel descriptor, as read by the host
+#[repr(C)]
+struct Channel {
+    name: *const u8,
+    buffer: *mut u8,
+    size: usize,
+    write: AtomicUsize,
+    read: AtomicUsize,
+    flags: AtomicUsize,
+}
+
+impl Channel {
+    const fn new() -> Self {
+        Self {
+            name: ptr::null(),
+            buffer: ptr::null_mut(),
+            size: 0,
+            write: AtomicUsize::new(0),
+            read: AtomicUsize::new(0),
+            flags: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of bytes that can be written without overwriting unread data.
+    fn free_space(&self) -> usize {
+        let write = self.write.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        // One byte is always left empty to tell a full buffer from an empty one
+        match read > write {
+            true => read - write - 1,
+            false => self.size - write + read - 1,
+        }
+    }
+
+    /// Copies as many bytes of `data` as fit in the buffer and returns how many were copied.
+    fn push(&self, data: &[u8]) -> usize {
+        let count = data.len().min(self.free_space());
+        let mut write = self.write.load(Ordering::Relaxed);
+        for byte in &data[..count] {
+            // SAFETY: `write` is within the bounds of the buffer
+            unsafe { self.buffer.add(write).write_volatile(*byte) };
+            write = if write + 1 == self.size { 0 } else { write + 1 };
+        }
+        self.write.store(write, Ordering::Release);
+        count
+    }
+
+    /// Moves as many bytes as available to `data` and returns how many were moved.
+    fn pop(&self, data: &mut [u8]) -> usize {
+        let write = self.write.load(Ordering::Acquire);
+        let mut read = self.read.load(Ordering::Relaxed);
+        let mut count = 0;
+        for byte in data.iter_mut() {
+            if read == write {
+                break;
+            }
+            // SAFETY: `read` is within the bounds of the buffer
+            *byte = unsafe { self.buffer.add(read).read_volatile() };
+            read = if read + 1 == self.size { 0 } else { read + 1 };
+            count += 1;
+        }
+        self.read.store(read, Ordering::Release);
+        count
+    }
+
+    /// Returns true if the buffer holds no data.
+    fn is_empty(&self) -> bool {
+        self.read.load(Ordering::Acquire) == self.write.load(Ordering::Acquire)
+    }
+}
+
+/// RTT control block, as read by the host
+#[repr(C)]
+struct ControlBlock {
+    id: [u8; 16],
+    max_up_channels: usize,
+    max_down_channels: usize,
+    up: Channel,
+    down: Channel,
+}
+
+/// Wrapper to place the control block in a `static`
+struct ControlBlockCell(UnsafeCell<ControlBlock>);
+
+// SAFETY: the channels are only accessed through `UpChannel` and `DownChannel`,
+// which are handed out once by `init`.
+unsafe impl Sync for ControlBlockCell {}
+
+#[link_section = ".rtt"]
+static CONTROL_BLOCK: ControlBlockCell = ControlBlockCell(UnsafeCell::new(ControlBlock {
+    id: [0; 16],
+    max_up_channels: 0,
+    max_down_channels: 0,
+    up: Channel::new(),
+    down: Channel::new(),
+}));
+
+/// Whether the channels were handed out
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Initializes the RTT control block and returns the up and down channels.
+///
+/// Returns `None` if the channels were already handed out.
+///
+/// # Panics
+///
+/// Panics if any of the buffers holds less than two bytes.
+pub fn init(
+    up_buf: &'static mut [u8],
+    down_buf: &'static mut [u8],
+) -> Option<(UpChannel, DownChannel)> {
+    assert!(up_buf.len() >= 2 && down_buf.len() >= 2);
+    critical_section::with(|_| {
+        if TAKEN.load(Ordering::Relaxed) {
+            return None;
+        }
+        TAKEN.store(true, Ordering::Relaxed);
+
+        let cb = CONTROL_BLOCK.0.get();
+        // SAFETY: the control block is not shared yet, and the ID is written last
+        // so the probe does not find a partially initialized control block
+        unsafe {
+            ptr::write_volatile(ptr::addr_of_mut!((*cb).id), [0; 16]);
+            (*cb).max_up_channels = 1;
+            (*cb).max_down_channels = 1;
+            for (channel, buf) in [(&mut (*cb).up, up_buf), (&mut (*cb).down, down_buf)] {
+                channel.name = CHANNEL_NAME.as_ptr();
+                channel.buffer = buf.as_mut_ptr();
+                channel.size = buf.len();
+                channel.write.store(0, Ordering::Relaxed);
+                channel.read.store(0, Ordering::Relaxed);
+                channel
+                    .flags
+                    .store(ChannelMode::NoBlockSkip as usize, Ordering::Relaxed);
+            }
+            core::sync::atomic::fence(Ordering::SeqCst);
+            ptr::write_volatile(ptr::addr_of_mut!((*cb).id), *ID);
+        }
+        Some((UpChannel(()), DownChannel(())))
+    })
+}
+
+/// RTT up channel (target to host)
+pub struct UpChannel(());
+
+impl UpChannel {
+    fn channel(&self) -> &Channel {
+        // SAFETY: the channel is initialized, and the target only writes the `write` index
+        unsafe { &(*CONTROL_BLOCK.0.get()).up }
+    }
+
+    /// Returns the behavior of the channel when its buffer is full.
+    pub fn mode(&self) -> ChannelMode {
+        match self.channel().flags.load(Ordering::Relaxed) & 0b11 {
+            0 => ChannelMode::NoBlockSkip,
+            1 => ChannelMode::NoBlockTrim,
+            _ => ChannelMode::BlockIfFull,
+        }
+    }
+
+    /// Sets the behavior of the channel when its buffer is full.
+    pub fn set_mode(&mut self, mode: ChannelMode) {
+        let flags = &self.channel().flags;
+        flags.store(
+            (flags.load(Ordering::Relaxed) & !0b11) | mode as usize,
+            Ordering::Relaxed,
+        );
+    }
+}
+
+impl embedded_io::ErrorType for UpChannel {
+    type Error = ErrorKind;
+}
+
+impl embedded_io::Write for UpChannel {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let channel = self.channel();
+        match self.mode() {
+            ChannelMode::NoBlockSkip => {
+                if channel.free_space() >= buf.len() {
+                    channel.push(buf);
+                }
+                Ok(buf.len())
+            }
+            ChannelMode::NoBlockTrim => {
+                channel.push(buf);
+                Ok(buf.len())
+            }
+            ChannelMode::BlockIfFull => loop {
+                match channel.push(buf) {
+                    0 if !buf.is_empty() => continue, // wait for the host
+                    count => return Ok(count),
+                }
+            },
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        // Without a blocking mode, the host may never read the buffer
+        if self.mode() == ChannelMode::BlockIfFull {
+            while !self.channel().is_empty() {}
+        }
+        Ok(())
+    }
+}
+
+/// RTT down channel (host to target)
+pub struct DownChannel(());
+
+impl DownChannel {
+    fn channel(&self) -> &Channel {
+        // SAFETY: the channel is initialized, and the target only writes the `read` index
+        unsafe { &(*CONTROL_BLOCK.0.get()).down }
+    }
+}
+
+impl embedded_io::ErrorType for DownChannel {
+    type Error = ErrorKind;
+}
+
+impl embedded_io::ReadReady for DownChannel {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.channel().is_empty())
+    }
+}
+
+impl embedded_io::Read for DownChannel {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let channel = self.channel();
+        while channel.is_empty() {} // first byte may block
+        Ok(channel.pop(buf))
+    }
+}
@@ -0,0 +1,680 @@
+//! Interactive command shell over the serial console
+//!
+//! [`Shell`] reads characters from any [`embedded_io::Read`] implementation (e.g., the
+//! [`Rx`](e310x_hal::serial::Rx) returned by [`configure`](crate::stdout::configure)) and
+//! prints to stdout, so it follows the sink selected in the [`stdout`](crate::stdout) module.
+//!
+//! The line editor supports backspace, `Ctrl-C` and `Ctrl-U` (discard the line), and
+//! `Ctrl-W` (delete the last word). Escape sequences (e.g., arrow keys) are ignored.
+//!
+//! # Built-in commands
+//!
+//! | Command                                   | Description                                  |
+//! |-------------------------------------------|----------------------------------------------|
+//! | `help`                                    | Lists the available commands                 |
+//! | `gpio <pin> [read]`                       | Reads a GPIO pin                             |
+//! | `gpio <pin> write <0\|1>`                 | Sets the output value of a GPIO pin          |
+//! | `gpio <pin> mode <in\|pullup\|out\|iof0\|iof1>` | Configures a GPIO pin                  |
+//! | `pwm <0-2> <1-3> [duty]`                  | Reads or sets the duty of a PWM comparator   |
+//! | `i2c scan`                                | Lists the devices on the I2C bus             |
+//! | `i2c read <addr> <len>`                   | Reads bytes from an I2C device               |
+//! | `i2c write <addr> <bytes>...`             | Writes bytes to an I2C device                |
+//! | `spi <bytes>...`                          | Full-duplex transfer with the SPI device     |
+//! | `peek <addr>`                             | Reads a 32-bit memory-mapped register        |
+//! | `poke <addr> <value>`                     | Writes a 32-bit memory-mapped register       |
+//! | `clocks`                                  | Prints the clock frequencies                 |
+//! | `reset`                                   | Prints the last wakeup and reset cause       |
+//!
+//! Numbers are decimal, or hexadecimal with a `0x` prefix. The `i2c`, `spi`, and `clocks`
+//! commands need the corresponding resource (see [`Shell::with_i2c`], [`Shell::with_spi`],
+//! and [`Shell::with_clocks`]). The I2C bus is only available on FE310-G002 boards, and
+//! `i2c scan` relies on [`I2c::scan`](e310x_hal::i2c::I2c::scan).
+//!
+//! # Example
+//!```ignore
+//! let mut rx = hifive1::stdout::configure(p.UART0, pin!(pins, uart0_tx), pin!(pins, uart0_rx), 115_200.bps(), clocks);
+//! let mut shell = Shell::new().with_clocks(clocks).with_i2c(&mut i2c);
+//! shell
+//!     .register(Command {
+//!         name: "hello",
+//!         help: "Says hello",
+//!         handler: |_, _| {
+//!             sprintln!("Hello!");
+//!             Ok(())
+//!         },
+//!     })
+//!     .unwrap();
+//! shell.run(&mut rx); // or `shell.run_async(&mut rx).await` with the `async` feature
+//!```
+
+use crate::{sprint, sprintln};
+use e310x_hal::{
+    clock::Clocks,
+    e310x::{Gpio0, Pmu, Pwm0, Pwm1, Pwm2},
+    pmu::PMUExt,
+    prelude::{embedded_hal::spi::SpiDevice, embedded_io},
+};
+#[cfg(any(
+    feature = "board-hifive1-revb",
+    feature = "board-redv",
+    feature = "board-lofive-r1"
+))]
+use {
+    core::time::Duration,
+    e310x_hal::{
+        i2c::{I2c, I2cX, ScanResult},
+        prelude::embedded_hal,
+    },
+};
+
+/// Maximum length of a command line
+pub const MAX_LINE_LEN: usize = 80;
+
+/// Maximum number of arguments of a command, including its name
+pub const MAX_ARGS: usize = 16;
+
+/// Maximum number of user commands
+pub const MAX_COMMANDS: usize = 16;
+
+/// Prompt printed before each command line
+const PROMPT: &str = "> ";
+
+/// Time given to each address to complete its probe during an I2C scan
+#[cfg(any(
+    feature = "board-hifive1-revb",
+    feature = "board-redv",
+    feature = "board-lofive-r1"
+))]
+const I2C_SCAN_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Errors of the shell commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    /// The command was called with the wrong number of arguments
+    Usage,
+    /// An argument is not valid
+    InvalidArgument,
+    /// The resource needed by the command is not attached to the shell
+    Unavailable,
+    /// The bus transaction failed
+    Bus,
+}
+
+/// Error returned when all the user command slots are in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandTableFull;
+
+/// Handler of a shell command, called with the shell resources and the command arguments
+///
+/// The first argument is the name of the command.
+pub type CommandFn = fn(&mut Resources<'_>, &[&str]) -> Result<(), CommandError>;
+
+/// Shell command
+#[derive(Clone, Copy)]
+pub struct Command {
+    /// Name of the command
+    pub name: &'static str,
+    /// One-line description of the command, printed by `help`
+    pub help: &'static str,
+    /// Handler of the command
+    pub handler: CommandFn,
+}
+
+/// Object-safe subset of [`I2c`] used by the built-in commands
+#[cfg(any(
+    feature = "board-hifive1-revb",
+    feature = "board-redv",
+    feature = "board-lofive-r1"
+))]
+trait I2cBus {
+    fn scan(&mut self) -> Result<ScanResult, CommandError>;
+    fn write(&mut self, address: u8, data: &[u8]) -> Result<(), CommandError>;
+    fn read(&mut self, address: u8, buf: &mut [u8]) -> Result<(), CommandError>;
+}
+
+#[cfg(any(
+    feature = "board-hifive1-revb",
+    feature = "board-redv",
+    feature = "board-lofive-r1"
+))]
+impl<I2C: I2cX, PINS> I2cBus for I2c<I2C, PINS> {
+    fn scan(&mut self) -> Result<ScanResult, CommandError> {
+        I2c::scan(self, I2C_SCAN_TIMEOUT).map_err(|_| CommandError::Bus)
+    }
+
+    fn write(&mut self, address: u8, data: &[u8]) -> Result<(), CommandError> {
+        embedded_hal::i2c::I2c::write(self, address, data).map_err(|_| CommandError::Bus)
+    }
+
+    fn read(&mut self, address: u8, buf: &mut [u8]) -> Result<(), CommandError> {
+        embedded_hal::i2c::I2c::read(self, address, buf).map_err(|_| CommandError::Bus)
+    }
+}
+
+/// Object-safe subset of [`SpiDevice`] used by the built-in commands
+trait SpiBus {
+    fn transfer_in_place(&mut self, buf: &mut [u8]) -> Result<(), CommandError>;
+}
+
+impl<T: SpiDevice> SpiBus for T {
+    fn transfer_in_place(&mut self, buf: &mut [u8]) -> Result<(), CommandError> {
+        SpiDevice::transfer_in_place(self, buf).map_err(|_| CommandError::Bus)
+    }
+}
+
+/// Resources available to the shell commands
+#[derive(Default)]
+pub struct Resources<'a> {
+    clocks: Option<Clocks>,
+    #[cfg(any(
+        feature = "board-hifive1-revb",
+        feature = "board-redv",
+        feature = "board-lofive-r1"
+    ))]
+    i2c: Option<&'a mut dyn I2cBus>,
+    spi: Option<&'a mut dyn SpiBus>,
+}
+
+impl Resources<'_> {
+    /// Returns the clock configuration, if attached.
+    pub fn clocks(&self) -> Option<Clocks> {
+        self.clocks
+    }
+}
+
+/// State of the escape sequence parser
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    Esc,
+    Csi,
+}
+
+/// Interactive command shell
+pub struct Shell<'a> {
+    resources: Resources<'a>,
+    commands: [Option<Command>; MAX_COMMANDS],
+    line: [u8; MAX_LINE_LEN],
+    len: usize,
+    escape: Escape,
+    /// Whether the last character was a carriage return, to treat CR LF as a single line end
+    cr: bool,
+}
+
+impl Default for Shell<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// Creates a new shell with the built-in commands only.
+    pub fn new() -> Self {
+        Self {
+            resources: Resources::default(),
+            commands: [None; MAX_COMMANDS],
+            line: [0; MAX_LINE_LEN],
+            len: 0,
+            escape: Escape::None,
+            cr: false,
+        }
+    }
+
+    /// Attaches the clock configuration, used by the `clocks` command.
+    pub fn with_clocks(mut self, clocks: Clocks) -> Self {
+        self.resources.clocks = Some(clocks);
+        self
+    }
+
+    /// Attaches an I2C bus, used by the `i2c` command.
+    #[cfg(any(
+        feature = "board-hifive1-revb",
+        feature = "board-redv",
+        feature = "board-lofive-r1"
+    ))]
+    pub fn with_i2c<I2C: I2cX, PINS>(mut self, i2c: &'a mut I2c<I2C, PINS>) -> Self {
+        self.resources.i2c = Some(i2c);
+        self
+    }
+
+    /// Attaches an SPI device, used by the `spi` command.
+    pub fn with_spi<S: SpiDevice>(mut self, spi: &'a mut S) -> Self {
+        self.resources.spi = Some(spi);
+        self
+    }
+
+    /// Registers a user command.
+    ///
+    /// User commands take precedence over the built-in commands with the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if all the user command slots are in use.
+    pub fn register(&mut self, command: Command) -> Result<(), CommandTableFull> {
+        let slot = self
+            .commands
+            .iter_mut()
+            .find(|c| c.is_none())
+            .ok_or(CommandTableFull)?;
+        *slot = Some(command);
+        Ok(())
+    }
+
+    /// Prints the prompt.
+    pub fn prompt(&self) {
+        sprint!(PROMPT);
+    }
+
+    /// Processes an input character, running the command once the line is complete.
+    pub fn process_byte(&mut self, byte: u8) {
+        match (self.escape, byte) {
+            (Escape::None, 0x1b) => self.escape = Escape::Esc,
+            (Escape::Esc, b'[') => self.escape = Escape::Csi,
+            // CSI sequences end with a byte in the 0x40..=0x7e range
+            (Escape::Csi, 0x40..=0x7e) | (Escape::Esc, _) => self.escape = Escape::None,
+            (Escape::Csi, _) => {}
+            (Escape::None, b'\n') if self.cr => {}
+            (Escape::None, b'\r' | b'\n') => {
+                sprintln!();
+                self.execute();
+                self.len = 0;
+                self.prompt();
+            }
+            // Backspace and delete
+            (Escape::None, 0x08 | 0x7f) if self.len > 0 => self.erase(),
+            // Ctrl-C and Ctrl-U
+            (Escape::None, 0x03 | 0x15) => {
+                self.len = 0;
+                sprintln!();
+                self.prompt();
+            }
+            // Ctrl-W
+            (Escape::None, 0x17) => {
+                while self.len > 0 && self.line[self.len - 1] == b' ' {
+                    self.erase();
+                }
+                while self.len > 0 && self.line[self.len - 1] != b' ' {
+                    self.erase();
+                }
+            }
+            (Escape::None, 0x20..=0x7e) if self.len < MAX_LINE_LEN => {
+                self.line[self.len] = byte;
+                self.len += 1;
+                let echo = [byte];
+                // SAFETY: printable ASCII characters are valid UTF-8
+                sprint!(unsafe { core::str::from_utf8_unchecked(&echo) });
+            }
+            _ => {}
+        }
+        self.cr = byte == b'\r';
+    }
+
+    /// Runs the shell, reading characters from `rx` forever.
+    pub fn run<R: embedded_io::Read>(&mut self, rx: &mut R) -> ! {
+        self.prompt();
+        let mut buf = [0; 16];
+        loop {
+            if let Ok(n) = rx.read(&mut buf) {
+                buf[..n].iter().for_each(|byte| self.process_byte(*byte));
+            }
+        }
+    }
+
+    /// Runs the shell, reading characters from `rx` asynchronously forever.
+    #[cfg(feature = "async")]
+    pub async fn run_async<R: embedded_io_async::Read>(&mut self, rx: &mut R) -> ! {
+        self.prompt();
+        let mut buf = [0; 16];
+        loop {
+            if let Ok(n) = rx.read(&mut buf).await {
+                buf[..n].iter().for_each(|byte| self.process_byte(*byte));
+            }
+        }
+    }
+
+    /// Removes the last character of the line.
+    fn erase(&mut self) {
+        self.len -= 1;
+        sprint!("\x08 \x08");
+    }
+
+    /// Runs the command in the line buffer.
+    fn execute(&mut self) {
+        // SAFETY: only printable ASCII characters are stored in the line buffer
+        let line = unsafe { core::str::from_utf8_unchecked(&self.line[..self.len]) };
+        let mut args = [""; MAX_ARGS];
+        let mut n_args = 0;
+        for arg in line.split_whitespace() {
+            if n_args == MAX_ARGS {
+                sprintln!("error: too many arguments");
+                return;
+            }
+            args[n_args] = arg;
+            n_args += 1;
+        }
+        let args = &args[..n_args];
+        let Some(name) = args.first() else {
+            return;
+        };
+
+        if *name == "help" {
+            self.help();
+            return;
+        }
+        let command = self
+            .commands
+            .iter()
+            .flatten()
+            .chain(BUILTINS.iter())
+            .find(|c| c.name == *name);
+        match command {
+            Some(command) => {
+                if let Err(e) = (command.handler)(&mut self.resources, args) {
+                    sprintln!("error: {:?}", e);
+                }
+            }
+            None => sprintln!("unknown command: {}", name),
+        }
+    }
+
+    /// Prints the available commands.
+    fn help(&self) {
+        sprintln!("{:<8} Lists the available commands", "help");
+        for command in self.commands.iter().flatten().chain(BUILTINS.iter()) {
+            sprintln!("{:<8} {}", command.name, command.help);
+        }
+    }
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number.
+pub fn parse_u32(s: &str) -> Result<u32, CommandError> {
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|_| CommandError::InvalidArgument)
+}
+
+/// Parses a byte argument.
+fn parse_u8(s: &str) -> Result<u8, CommandError> {
+    u8::try_from(parse_u32(s)?).map_err(|_| CommandError::InvalidArgument)
+}
+
+/// Prints a byte slice in hexadecimal.
+fn print_bytes(bytes: &[u8]) {
+    for byte in bytes {
+        sprint!("{:02x} ", byte);
+    }
+    sprintln!();
+}
+
+/// Built-in commands, except `help`
+const BUILTINS: [Command; 8] = [
+    Command {
+        name: "gpio",
+        help: "<pin> [read | write <0|1> | mode <in|pullup|out|iof0|iof1>]",
+        handler: gpio,
+    },
+    Command {
+        name: "pwm",
+        help: "<0-2> <1-3> [duty]: reads or sets the duty of a PWM comparator",
+        handler: pwm,
+    },
+    Command {
+        name: "i2c",
+        help: "scan | read <addr> <len> | write <addr> <bytes>...",
+        handler: i2c,
+    },
+    Command {
+        name: "spi",
+        help: "<bytes>...: full-duplex transfer with the SPI device",
+        handler: spi,
+    },
+    Command {
+        name: "peek",
+        help: "<addr>: reads a 32-bit register",
+        handler: peek,
+    },
+    Command {
+        name: "poke",
+        help: "<addr> <value>: writes a 32-bit register",
+        handler: poke,
+    },
+    Command {
+        name: "clocks",
+        help: "Prints the clock frequencies",
+        handler: clocks,
+    },
+    Command {
+        name: "reset",
+        help: "Prints the last wakeup and reset cause",
+        handler: reset,
+    },
+];
+
+/// Sets or clears the bit of a pin in a GPIO register.
+macro_rules! set_pin_bit {
+    ($reg:expr, $pin:expr, $bit:expr) => {
+        $reg.modify(|r, w| unsafe {
+            w.bits(match $bit {
+                true => r.bits() | (1 << $pin),
+                false => r.bits() & !(1 << $pin),
+            })
+        })
+    };
+}
+
+fn gpio(_: &mut Resources<'_>, args: &[&str]) -> Result<(), CommandError> {
+    let pin = match args.get(1) {
+        Some(pin) => parse_u32(pin)?,
+        None => return Err(CommandError::Usage),
+    };
+    if pin > 31 {
+        return Err(CommandError::InvalidArgument);
+    }
+    // SAFETY: the registers are modified within a critical section
+    let gpio = unsafe { Gpio0::steal() };
+    critical_section::with(|_| match args[2..] {
+        [] | ["read"] => {
+            let value = gpio.input_val().read().bits() >> pin & 1;
+            sprintln!("gpio{}: {}", pin, value);
+            Ok(())
+        }
+        ["write", value] => {
+            let bit = match value {
+                "0" => false,
+                "1" => true,
+                _ => return Err(CommandError::InvalidArgument),
+            };
+            set_pin_bit!(gpio.output_val(), pin, bit);
+            Ok(())
+        }
+        ["mode", mode] => {
+            match mode {
+                "in" | "pullup" => {
+                    set_pin_bit!(gpio.output_en(), pin, false);
+                    set_pin_bit!(gpio.pullup(), pin, mode == "pullup");
+                    set_pin_bit!(gpio.input_en(), pin, true);
+                    set_pin_bit!(gpio.iof_en(), pin, false);
+                }
+                "out" => {
+                    set_pin_bit!(gpio.drive(), pin, false);
+                    set_pin_bit!(gpio.out_xor(), pin, false);
+                    set_pin_bit!(gpio.output_en(), pin, true);
+                    set_pin_bit!(gpio.iof_en(), pin, false);
+                }
+                "iof0" | "iof1" => {
+                    set_pin_bit!(gpio.iof_sel(), pin, mode == "iof1");
+                    set_pin_bit!(gpio.iof_en(), pin, true);
+                }
+                _ => return Err(CommandError::InvalidArgument),
+            }
+            Ok(())
+        }
+        _ => Err(CommandError::Usage),
+    })
+}
+
+/// Optionally writes a PWM comparator, and returns its value.
+macro_rules! cmp_access {
+    ($reg:expr, $duty:expr) => {{
+        if let Some(duty) = $duty {
+            $reg.write(|w| unsafe { w.value().bits(duty as u16) });
+        }
+        $reg.read().value().bits()
+    }};
+}
+
+fn pwm(_: &mut Resources<'_>, args: &[&str]) -> Result<(), CommandError> {
+    let (index, cmp) = match args {
+        [_, index, cmp] | [_, index, cmp, _] => (parse_u32(index)?, parse_u32(cmp)?),
+        _ => return Err(CommandError::Usage),
+    };
+    // SAFETY: the comparator registers are only accessed by index
+    let pwm = unsafe {
+        match index {
+            0 => &*Pwm0::ptr(),
+            1 => &*Pwm1::ptr(),
+            2 => &*Pwm2::ptr(),
+            _ => return Err(CommandError::InvalidArgument),
+        }
+    };
+    let duty = match args.get(3) {
+        Some(duty) => Some(parse_u32(duty)?),
+        None => None,
+    };
+    // PWM0 comparators are 8-bit wide, PWM1 and PWM2 comparators are 16-bit wide
+    let max = if index == 0 {
+        u8::MAX as u32
+    } else {
+        u16::MAX as u32
+    };
+    if duty.is_some_and(|duty| duty > max) {
+        return Err(CommandError::InvalidArgument);
+    }
+    let value = match cmp {
+        1 => cmp_access!(pwm.cmp1(), duty),
+        2 => cmp_access!(pwm.cmp2(), duty),
+        3 => cmp_access!(pwm.cmp3(), duty),
+        _ => return Err(CommandError::InvalidArgument),
+    };
+    sprintln!(
+        "pwm{} cmp{}: {} (period {})",
+        index,
+        cmp,
+        value,
+        pwm.cmp0().read().value().bits()
+    );
+    Ok(())
+}
+
+#[cfg(any(
+    feature = "board-hifive1-revb",
+    feature = "board-redv",
+    feature = "board-lofive-r1"
+))]
+fn i2c(resources: &mut Resources<'_>, args: &[&str]) -> Result<(), CommandError> {
+    let i2c = resources.i2c.as_mut().ok_or(CommandError::Unavailable)?;
+    match args[1..] {
+        ["scan"] => {
+            for address in i2c.scan()?.iter() {
+                sprint!("0x{:02x} ", address);
+            }
+            sprintln!();
+            Ok(())
+        }
+        ["read", address, len] => {
+            let address = parse_u8(address)?;
+            let mut buf = [0; 32];
+            let buf = buf
+                .get_mut(..parse_u32(len)? as usize)
+                .ok_or(CommandError::InvalidArgument)?;
+            i2c.read(address, buf)?;
+            print_bytes(buf);
+            Ok(())
+        }
+        ["write", address, ref bytes @ ..] => {
+            let address = parse_u8(address)?;
+            let mut buf = [0; MAX_ARGS];
+            for (b, arg) in buf.iter_mut().zip(bytes) {
+                *b = parse_u8(arg)?;
+            }
+            i2c.write(address, &buf[..bytes.len()])
+        }
+        _ => Err(CommandError::Usage),
+    }
+}
+
+/// Boards without I2C controller have no I2C bus to attach.
+#[cfg(not(any(
+    feature = "board-hifive1-revb",
+    feature = "board-redv",
+    feature = "board-lofive-r1"
+)))]
+fn i2c(_resources: &mut Resources<'_>, _args: &[&str]) -> Result<(), CommandError> {
+    Err(CommandError::Unavailable)
+}
+
+fn spi(resources: &mut Resources<'_>, args: &[&str]) -> Result<(), CommandError> {
+    let spi = resources.spi.as_mut().ok_or(CommandError::Unavailable)?;
+    let bytes = &args[1..];
+    if bytes.is_empty() {
+        return Err(CommandError::Usage);
+    }
+    let mut buf = [0; MAX_ARGS];
+    for (b, arg) in buf.iter_mut().zip(bytes) {
+        *b = parse_u8(arg)?;
+    }
+    let buf = &mut buf[..bytes.len()];
+    spi.transfer_in_place(buf)?;
+    print_bytes(buf);
+    Ok(())
+}
+
+/// Parses a 32-bit aligned address.
+fn parse_address(s: &str) -> Result<*mut u32, CommandError> {
+    match parse_u32(s)? {
+        address if address % 4 == 0 => Ok(address as *mut u32),
+        _ => Err(CommandError::InvalidArgument),
+    }
+}
+
+fn peek(_: &mut Resources<'_>, args: &[&str]) -> Result<(), CommandError> {
+    let [_, address] = args else {
+        return Err(CommandError::Usage);
+    };
+    let address = parse_address(address)?;
+    // SAFETY: the user is responsible for reading a valid address
+    let value = unsafe { address.read_volatile() };
+    sprintln!("{:#010x}: {:#010x}", address as usize, value);
+    Ok(())
+}
+
+fn poke(_: &mut Resources<'_>, args: &[&str]) -> Result<(), CommandError> {
+    let [_, address, value] = args else {
+        return Err(CommandError::Usage);
+    };
+    let address = parse_address(address)?;
+    let value = parse_u32(value)?;
+    // SAFETY: the user is responsible for writing a valid address
+    unsafe { address.write_volatile(value) };
+    Ok(())
+}
+
+fn clocks(resources: &mut Resources<'_>, _: &[&str]) -> Result<(), CommandError> {
+    let clocks = resources.clocks.ok_or(CommandError::Unavailable)?;
+    sprintln!("coreclk: {} Hz", clocks.coreclk().0);
+    sprintln!("tlclk:   {} Hz", clocks.tlclk().0);
+    sprintln!("lfclk:   {} Hz", clocks.lfclk().0);
+    Ok(())
+}
+
+fn reset(_: &mut Resources<'_>, _: &[&str]) -> Result<(), CommandError> {
+    // SAFETY: the cause register is read-only
+    let pmu = unsafe { Pmu::steal() };
+    match pmu.wakeup_cause() {
+        Ok(cause) => sprintln!("{:?}", cause),
+        Err(_) => sprintln!("unknown"),
+    }
+    Ok(())
+}
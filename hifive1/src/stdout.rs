@@ -1,9 +1,38 @@
 //! Stdout based on the UART hooked up to FTDI or J-Link
+//!
+//! # Sinks
+//!
+//! [`sprint!`](crate::sprint) and [`sprintln!`](crate::sprintln) write to the sink selected
+//! at runtime. Ready-made sinks are provided for UART0 ([`configure`], [`configure_deferred`]),
+//! UART1 (`configure_uart1`, on FE310-G002 boards), semihosting (`configure_semihosting`,
+//! with the `semihosting` feature), and RTT (`configure_rtt`, with the `rtt` feature).
+//! Any other [`embedded_io::Write`] implementation can be registered with [`set_sink`].
+//!
+//! With the `ufmt` feature, [`uprint!`](crate::uprint) and [`uprintln!`](crate::uprintln)
+//! write to the same sink with [`ufmt`] instead of `core::fmt`, which is much smaller.
+//!
+//! # Deferred mode
+//!
+//! By default, [`sprint!`](crate::sprint) and [`sprintln!`](crate::sprintln) busy-wait on
+//! the UART TX FIFO. With [`configure_deferred`], they copy the text to a ring buffer instead,
+//! and the UART0 TX watermark interrupt sends it in the background. In this mode:
+//!
+//! - The UART0 external interrupt must be enabled in the PLIC (e.g., with [`Rx::enable_exti`]).
+//! - Without the `async` feature, the UART0 interrupt handler must call
+//!   [`on_buffered_interrupt::<Uart0>()`](e310x_hal::serial::on_buffered_interrupt).
+//! - The [`OverflowPolicy`] selects what happens when the ring buffer is full, and
+//!   [`dropped_bytes`] counts the bytes lost with [`OverflowPolicy::Drop`].
+//! - [`flush`] must be called before sleeping or resetting the core to send the pending text.
+//!
+//! The ring buffer is lock-free between the writers and the interrupt handler. Writers only
+//! hold a critical section while copying the text, so they do not wait for the UART.
 
 use core::{
-    fmt::{self, Result, Write},
+    cell::Cell,
+    fmt::{self, Write},
     ptr,
 };
+use critical_section::Mutex;
 use e310x_hal::{
     clock::Clocks,
     e310x::Uart0,
@@ -11,22 +40,170 @@ use e310x_hal::{
         gpio0::{Pin16, Pin17},
         NoInvert, IOF0,
     },
-    serial::{Rx, Serial, Tx},
+    prelude::embedded_io::{self, ErrorKind, WriteReady},
+    serial::{BufferedTx, Rx, Serial, Tx},
     stdout::Stdout,
     time::Bps,
 };
+#[cfg(any(
+    feature = "board-hifive1-revb",
+    feature = "board-redv",
+    feature = "board-lofive-r1"
+))]
+use e310x_hal::{
+    e310x::Uart1,
+    gpio::gpio0::{Pin18, Pin23},
+};
+
+type Tx0Pin = Pin17<IOF0<NoInvert>>;
+type Rx0Pin = Pin16<IOF0<NoInvert>>;
+
+/// Type-erased stdout sink
+pub type Sink = dyn embedded_io::Write<Error = ErrorKind>;
+
+/// Behavior of the deferred stdout when its ring buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the text that does not fit and count the dropped bytes
+    Drop,
+    /// Wait for room in the ring buffer, sending bytes from the caller context if needed
+    Block,
+}
+
+/// Number of bytes dropped by the deferred stdout
+static DROPPED: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
 
-struct SerialWrapper(Tx<Uart0, Pin17<IOF0<NoInvert>>>);
+/// Deferred stdout writer
+struct Deferred {
+    tx: BufferedTx<Uart0, Tx0Pin>,
+    policy: OverflowPolicy,
+}
+
+impl embedded_io::ErrorType for Deferred {
+    type Error = ErrorKind;
+}
+
+impl embedded_io::Write for Deferred {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            if self.tx.write_ready()? {
+                return self.tx.write(buf);
+            }
+            match self.policy {
+                OverflowPolicy::Drop => {
+                    critical_section::with(|cs| {
+                        let dropped = DROPPED.borrow(cs);
+                        dropped.set(dropped.get().saturating_add(buf.len() as u32));
+                    });
+                    return Ok(buf.len());
+                }
+                // Interrupts may be disabled here, so make progress from the caller context
+                OverflowPolicy::Block => self.tx.poll_tx(),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.tx.flush()
+    }
+}
+
+/// Semihosting stdout writer
+#[cfg(feature = "semihosting")]
+struct Semihosting(semihosting::io::Stdout);
+
+#[cfg(feature = "semihosting")]
+impl embedded_io::ErrorType for Semihosting {
+    type Error = ErrorKind;
+}
+
+#[cfg(feature = "semihosting")]
+impl embedded_io::Write for Semihosting {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        semihosting::io::Write::write(&mut self.0, buf).map_err(|_| ErrorKind::Other)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
 
-static mut STDOUT: Option<SerialWrapper> = None;
+/// Storage of the ready-made sinks
+enum SinkStorage {
+    Uart0(Tx<Uart0, Tx0Pin>),
+    Deferred(Deferred),
+    #[cfg(any(
+        feature = "board-hifive1-revb",
+        feature = "board-redv",
+        feature = "board-lofive-r1"
+    ))]
+    Uart1(Tx<Uart1, Pin18<IOF0<NoInvert>>>),
+    #[cfg(feature = "semihosting")]
+    Semihosting(Semihosting),
+    #[cfg(feature = "rtt")]
+    Rtt(crate::rtt::UpChannel),
+}
 
-impl Write for SerialWrapper {
-    fn write_str(&mut self, s: &str) -> Result {
-        let mut stdout = Stdout(&mut self.0);
-        stdout.write_str(s)
+impl SinkStorage {
+    fn as_sink(&mut self) -> &mut Sink {
+        match self {
+            Self::Uart0(tx) => tx,
+            Self::Deferred(deferred) => deferred,
+            #[cfg(any(
+                feature = "board-hifive1-revb",
+                feature = "board-redv",
+                feature = "board-lofive-r1"
+            ))]
+            Self::Uart1(tx) => tx,
+            #[cfg(feature = "semihosting")]
+            Self::Semihosting(semihosting) => semihosting,
+            #[cfg(feature = "rtt")]
+            Self::Rtt(up) => up,
+        }
     }
 }
 
+static mut STORAGE: Option<SinkStorage> = None;
+
+static mut STDOUT: Option<&'static mut Sink> = None;
+
+/// Registers any [`embedded_io::Write`] implementation as stdout sink.
+///
+/// The previous sink, if any, is dropped.
+pub fn set_sink(sink: &'static mut Sink) {
+    critical_section::with(|_| {
+        unsafe { &mut *ptr::addr_of_mut!(STDOUT) }.replace(sink);
+        unsafe { &mut *ptr::addr_of_mut!(STORAGE) }.take();
+    });
+}
+
+/// Moves a ready-made sink to its storage and registers it as stdout sink.
+fn set_storage(storage: SinkStorage) {
+    critical_section::with(|_| {
+        // The previous sink may live in the storage, unregister it first
+        unsafe { &mut *ptr::addr_of_mut!(STDOUT) }.take();
+        let storage = unsafe { &mut *ptr::addr_of_mut!(STORAGE) }.insert(storage);
+        unsafe { &mut *ptr::addr_of_mut!(STDOUT) }.replace(storage.as_sink());
+    });
+}
+
+/// Configures the UART0 pins and returns the TX and RX halves.
+fn split<X, Y>(
+    uart: Uart0,
+    tx: Pin17<X>,
+    rx: Pin16<Y>,
+    baud_rate: Bps,
+    clocks: Clocks,
+) -> (Tx<Uart0, Tx0Pin>, Rx<Uart0, Rx0Pin>) {
+    let tx = tx.into_iof0();
+    let rx = rx.into_iof0();
+    let serial = Serial::new(uart, (tx, rx), baud_rate, clocks);
+    serial.split()
+}
+
 /// Configures stdout
 pub fn configure<X, Y>(
     uart: Uart0,
@@ -34,23 +211,82 @@ pub fn configure<X, Y>(
     rx: Pin16<Y>,
     baud_rate: Bps,
     clocks: Clocks,
-) -> Rx<Uart0, Pin16<IOF0<NoInvert>>> {
+) -> Rx<Uart0, Rx0Pin> {
+    let (tx, rx) = split(uart, tx, rx, baud_rate, clocks);
+    set_storage(SinkStorage::Uart0(tx));
+    rx
+}
+
+/// Configures stdout in deferred mode, using `buf` as ring buffer
+///
+/// See the [module documentation](self) for the interrupt requirements.
+///
+/// # Panics
+///
+/// Panics if the buffer is empty.
+pub fn configure_deferred<X, Y>(
+    uart: Uart0,
+    tx: Pin17<X>,
+    rx: Pin16<Y>,
+    baud_rate: Bps,
+    clocks: Clocks,
+    buf: &'static mut [u8],
+    policy: OverflowPolicy,
+) -> Rx<Uart0, Rx0Pin> {
+    let (tx, rx) = split(uart, tx, rx, baud_rate, clocks);
+    let deferred = Deferred {
+        tx: BufferedTx::new(tx, buf),
+        policy,
+    };
+    critical_section::with(|cs| DROPPED.borrow(cs).set(0));
+    set_storage(SinkStorage::Deferred(deferred));
+    rx
+}
+
+#[cfg(any(
+    feature = "board-hifive1-revb",
+    feature = "board-redv",
+    feature = "board-lofive-r1"
+))]
+/// Configures stdout on UART1
+pub fn configure_uart1<X, Y>(
+    uart: Uart1,
+    tx: Pin18<X>,
+    rx: Pin23<Y>,
+    baud_rate: Bps,
+    clocks: Clocks,
+) -> Rx<Uart1, Pin23<IOF0<NoInvert>>> {
     let tx = tx.into_iof0();
     let rx = rx.into_iof0();
     let serial = Serial::new(uart, (tx, rx), baud_rate, clocks);
     let (tx, rx) = serial.split();
-
-    critical_section::with(|_| {
-        unsafe { &mut *ptr::addr_of_mut!(STDOUT) }.replace(SerialWrapper(tx));
-    });
+    set_storage(SinkStorage::Uart1(tx));
     rx
 }
 
+/// Configures stdout on the semihosting console of the debugger
+///
+/// # Errors
+///
+/// Returns an error if the debugger does not provide a console.
+#[cfg(feature = "semihosting")]
+pub fn configure_semihosting() -> semihosting::io::Result<()> {
+    let stdout = semihosting::io::stdout()?;
+    set_storage(SinkStorage::Semihosting(Semihosting(stdout)));
+    Ok(())
+}
+
+/// Configures stdout on an RTT up channel
+#[cfg(feature = "rtt")]
+pub fn configure_rtt(up: crate::rtt::UpChannel) {
+    set_storage(SinkStorage::Rtt(up));
+}
+
 /// Writes string to stdout
 pub fn write_str(s: &str) {
     critical_section::with(|_| {
         if let Some(stdout) = unsafe { &mut *ptr::addr_of_mut!(STDOUT) } {
-            let _ = stdout.write_str(s);
+            let _ = Stdout(stdout).write_str(s);
         }
     });
 }
@@ -59,11 +295,53 @@ pub fn write_str(s: &str) {
 pub fn write_fmt(args: fmt::Arguments) {
     critical_section::with(|_| {
         if let Some(stdout) = unsafe { &mut *ptr::addr_of_mut!(STDOUT) } {
-            let _ = stdout.write_fmt(args);
+            let _ = Stdout(stdout).write_fmt(args);
+        }
+    });
+}
+
+/// Writes raw bytes to stdout, without newline translation
+#[cfg(feature = "defmt")]
+pub(crate) fn write_bytes(bytes: &[u8]) {
+    critical_section::with(|_| {
+        if let Some(stdout) = unsafe { &mut *ptr::addr_of_mut!(STDOUT) } {
+            let _ = stdout.write_all(bytes);
+        }
+    });
+}
+
+/// Calls `f` with a [`ufmt`] writer on stdout, within a critical section
+///
+/// Used by [`uprint!`](crate::uprint) and [`uprintln!`](crate::uprintln).
+#[cfg(feature = "ufmt")]
+#[doc(hidden)]
+pub fn with_uwriter(f: impl FnOnce(&mut Stdout<'_, &'static mut Sink>)) {
+    critical_section::with(|_| {
+        if let Some(stdout) = unsafe { &mut *ptr::addr_of_mut!(STDOUT) } {
+            f(&mut Stdout(stdout));
         }
     });
 }
 
+/// Blocks until all the text written to stdout is transmitted
+///
+/// In deferred mode, the pending text is sent from the caller context,
+/// so this function can be called with interrupts disabled (e.g., before sleeping or resetting).
+pub fn flush() {
+    critical_section::with(|_| {
+        if let Some(stdout) = unsafe { &mut *ptr::addr_of_mut!(STDOUT) } {
+            let _ = stdout.flush();
+        }
+    });
+}
+
+/// Returns the number of bytes dropped by the deferred stdout since it was configured.
+///
+/// It is always 0 in blocking mode and with [`OverflowPolicy::Block`].
+pub fn dropped_bytes() -> u32 {
+    critical_section::with(|cs| DROPPED.borrow(cs).get())
+}
+
 /// Macro for printing to the serial standard output
 #[macro_export]
 macro_rules! sprint {
@@ -88,3 +366,34 @@ macro_rules! sprintln {
         $crate::stdout::write_fmt(format_args!(concat!($s, "\n"), $($tt)*))
     };
 }
+
+/// Macro for printing to the serial standard output with [`ufmt`]
+///
+/// The application must depend on the `ufmt` crate.
+#[cfg(feature = "ufmt")]
+#[macro_export]
+macro_rules! uprint {
+    ($($tt:tt)*) => {
+        $crate::stdout::with_uwriter(|w| {
+            let _ = $crate::ufmt::uwrite!(w, $($tt)*);
+        })
+    };
+}
+
+/// Macro for printing to the serial standard output with [`ufmt`], with a newline.
+///
+/// The application must depend on the `ufmt` crate.
+#[cfg(feature = "ufmt")]
+#[macro_export]
+macro_rules! uprintln {
+    () => {
+        $crate::stdout::with_uwriter(|w| {
+            let _ = $crate::ufmt::uwrite!(w, "\n");
+        })
+    };
+    ($($tt:tt)*) => {
+        $crate::stdout::with_uwriter(|w| {
+            let _ = $crate::ufmt::uwriteln!(w, $($tt)*);
+        })
+    };
+}